@@ -1,14 +1,53 @@
 use dioxus::prelude::*;
+#[cfg(not(any(feature = "desktop", feature = "mobile")))]
+use std::time::Duration;
 
-use crate::router::Route;
+use crate::{components::auth_provider::AuthProvider, oauth2, router::Route};
+
+// claimed private-use scheme, the shell forwards the urls opened through it with `oauth2::deep_link::deliver`
+#[cfg(feature = "mobile")]
+const MOBILE_REDIRECT_URI: &str = "com.example.app:/oauth2redirect";
+
+#[cfg(feature = "mobile")]
+fn open_in_browser(url: &str) -> anyhow::Result<()> {
+    Ok(webbrowser::open(url)?)
+}
 
 #[component]
 pub fn App() -> Element {
+    let client = oauth2::azure::AuthorizationCodeFlowWithPKCE::default()
+        .with_client_id("00000000-0000-0000-0000-000000000000")
+        .with_scope("api://00000000-0000-0000-0000-000000000000/access")
+        .with_oidc_url("https://login.microsoftonline.com/{tenant_id}/v2.0/.well-known/openid-configuration");
+
+    // azure refresh tokens of single-page applications expire after 24 hours
+    #[cfg(not(any(feature = "desktop", feature = "mobile")))]
+    let client = client
+        .with_hybrid_flow()
+        .with_session_storage()
+        .with_refresh_token_max_age(Duration::from_secs(24 * 60 * 60));
+
+    // loopback redirects only carry the query, the hybrid flow needs the fragment
+    #[cfg(feature = "desktop")]
+    let client = client
+        .with_platform(oauth2::loopback::LoopbackPlatform::default())
+        .with_memory_storage();
+
+    // the shell registers the keychain with `oauth2::storage::register_secure_store`, until then the session stays in memory
+    #[cfg(all(feature = "mobile", not(feature = "desktop")))]
+    let client = client
+        .with_platform(oauth2::deep_link::DeepLinkPlatform::new(MOBILE_REDIRECT_URI, open_in_browser))
+        .with_secure_storage();
+
     rsx! {
         document::Link { rel: "icon", href: asset!("/assets/favicon.ico") }
         document::Link { rel: "stylesheet", href: asset!("/assets/main.css") }
 
 
-        Router::<Route> {}
+        AuthProvider {
+            client,
+
+            Router::<Route> {}
+        }
     }
 }
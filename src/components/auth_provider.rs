@@ -0,0 +1,30 @@
+use dioxus::prelude::*;
+
+use crate::{hooks::auth::AuthContext, oauth2};
+
+#[derive(Props, Clone)]
+pub struct AuthProviderProps {
+    client: oauth2::azure::AuthorizationCodeFlowWithPKCE,
+    children: Element,
+}
+
+impl PartialEq for AuthProviderProps {
+    fn eq(&self, other: &Self) -> bool {
+        // the client is only read on the first render
+        self.children == other.children
+    }
+}
+
+#[component]
+pub fn AuthProvider(props: AuthProviderProps) -> Element {
+    let auth = use_context_provider(|| AuthContext::new(props.client.clone()));
+
+    use_hook(|| {
+        auth.watch_connectivity();
+        auth.watch_session_expiry();
+    });
+
+    rsx! {
+        {props.children}
+    }
+}
@@ -0,0 +1,72 @@
+use dioxus::{logger::tracing, prelude::*};
+
+pub use crate::oauth2::requirements::{Rejection, Requirements};
+use crate::{
+    hooks::auth::{AuthState, use_auth},
+    oauth2,
+    router::Route,
+};
+
+#[component]
+pub fn GuardComponent(requirements: Requirements, children: Element) -> Element {
+    let auth = use_auth();
+    let navigator = use_navigator();
+
+    use_effect(move || match auth.state() {
+        AuthState::Unauthenticated => auth.login(),
+        AuthState::Authenticated(user_profile) => {
+            match requirements.check(user_profile.as_deref(), &auth.granted_scopes(), auth.now()) {
+                Err(Rejection::Forbidden(reason)) => {
+                    navigator.replace(Route::Forbidden { reason });
+                }
+                Err(Rejection::StaleAuthentication(max_age, reason)) => {
+                    tracing::info!("signing in again: {reason}");
+
+                    auth.login_with(
+                        oauth2::params::AuthorizationOptions::default()
+                            .with_prompt(oauth2::params::Prompt::Login)
+                            .with_max_age(max_age),
+                    )
+                }
+                Ok(()) => {}
+            }
+        }
+        _ => {}
+    });
+
+    match auth.state() {
+        AuthState::Authenticated(user_profile)
+            if requirements
+                .check(user_profile.as_deref(), &auth.granted_scopes(), auth.now())
+                .is_ok() =>
+        {
+            rsx! {
+                {children}
+            }
+        }
+        AuthState::DeviceAuthorization(device_authorization) => rsx! {
+            div {
+                class: "p-10 grid gap-5",
+
+                p {
+                    "Open {device_authorization.verification_uri} and enter the code"
+                }
+
+                code {
+                    class: "text-2xl",
+                    "{device_authorization.user_code}"
+                }
+            }
+        },
+        AuthState::Error(error) => rsx! {
+            p {
+                "{error}"
+            }
+        },
+        _ => rsx! {
+            p {
+                "Authorizing..."
+            }
+        },
+    }
+}
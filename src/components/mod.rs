@@ -1 +1,3 @@
+pub mod auth_provider;
+pub mod guard;
 pub mod navbar;
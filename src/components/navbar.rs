@@ -1,29 +1,79 @@
-use std::sync::Arc;
-
 use dioxus::{logger::tracing, prelude::*};
 
-use crate::oauth2;
+use crate::{hooks::auth::use_auth, oauth2, router::Route};
 
 #[component]
 pub fn NavbarComponent() -> Element {
-    let oauth2_client = use_context::<Arc<oauth2::azure::AuthorizationCodeFlowWithPKCE>>();
+    let auth = use_auth();
 
     let print_token = move |_| {
-        let oauth2_client = Arc::clone(&oauth2_client);
-
         spawn(async move {
-            if let Ok(Some(token)) = oauth2_client.acquire_token_silent().await {
-                tracing::info!("{token:?}");
+            match auth.get_token().await {
+                Ok(Some(token)) => {
+                    if token.is_stale {
+                        tracing::warn!("token expired while offline, refresh queued");
+                    }
+
+                    tracing::info!("{token:?}");
+                }
+                Ok(None) => {}
+                Err(error) => tracing::warn!("failed to get token: {error:?}"),
             }
         });
     };
 
+    let switch_account = move |_| {
+        auth.login_with(oauth2::params::AuthorizationOptions::default().with_prompt(oauth2::params::Prompt::SelectAccount))
+    };
+
+    let logout = move |_| auth.logout();
+
+    let renew_session = move |_| auth.renew_session();
+
     rsx! {
+        Link {
+            to: Route::Admin {},
+            class: "bg-blue-600 text-white p-4 rounded",
+
+            "Admin"
+        }
+
         button {
             onclick: print_token,
             class: "bg-green-600 text-white p-4 rounded",
 
             "Print Token"
         }
+
+        button {
+            onclick: switch_account,
+            class: "bg-gray-600 text-white p-4 rounded",
+
+            "Switch Account"
+        }
+
+        button {
+            onclick: logout,
+            class: "bg-red-600 text-white p-4 rounded",
+
+            "Logout"
+        }
+
+        if auth.is_session_expiring() {
+            button {
+                onclick: renew_session,
+                class: "bg-orange-500 text-white p-4 rounded",
+
+                "Session expires soon, renew"
+            }
+        }
+
+        if auth.is_offline() {
+            span {
+                class: "bg-yellow-500 text-white p-4 rounded",
+
+                "Offline"
+            }
+        }
     }
 }
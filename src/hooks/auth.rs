@@ -0,0 +1,295 @@
+use dioxus::{logger::tracing, prelude::*};
+use futures::StreamExt;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::oauth2;
+
+// ahead of the hard session expiry users are warned and offered to renew the session
+const SESSION_EXPIRY_WARNING: Duration = Duration::from_secs(10 * 60);
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthState {
+    Unauthenticated,
+    Authenticating,
+    DeviceAuthorization(Box<oauth2::device::DeviceAuthorizationResponse>),
+    Authenticated(Option<Box<oauth2::claims::UserProfile>>),
+    Error(String),
+}
+
+#[derive(Clone, Copy)]
+pub struct AuthContext {
+    state: Signal<AuthState>,
+    online: Signal<bool>,
+    session_expiring: Signal<bool>,
+    client: CopyValue<oauth2::azure::AuthorizationCodeFlowWithPKCE>,
+}
+
+impl AuthContext {
+    pub fn new(client: oauth2::azure::AuthorizationCodeFlowWithPKCE) -> Self {
+        Self {
+            state: Signal::new(AuthState::Unauthenticated),
+            online: Signal::new(client.is_online()),
+            session_expiring: Signal::new(false),
+            client: CopyValue::new(client),
+        }
+    }
+
+    pub fn state(&self) -> AuthState {
+        self.state.read().clone()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        !*self.online.read()
+    }
+
+    /// Whether the session ends soon, past its hard expiry an interactive login is needed.
+    pub fn is_session_expiring(&self) -> bool {
+        *self.session_expiring.read()
+    }
+
+    /// Current unix time on the provider clock.
+    pub fn now(&self) -> i64 {
+        self.client.read().clock().now()
+    }
+
+    /// Time left until the hard expiry of the session, if known.
+    pub fn session_expires_in(&self) -> Option<Duration> {
+        let session_expires_at = self.client.read().session_expires_at().ok().flatten()?;
+
+        Some(Duration::from_secs((session_expires_at - self.now()).max(0) as u64))
+    }
+
+    /// Watches the hard expiry of the session, warning ahead of it.
+    pub fn watch_session_expiry(&self) {
+        let auth = *self;
+        let mut session_expiring = self.session_expiring;
+
+        spawn(async move {
+            loop {
+                let session_expires_in = auth.session_expires_in();
+
+                let is_expiring = session_expires_in.is_some_and(|expires_in| expires_in <= SESSION_EXPIRY_WARNING);
+
+                if *session_expiring.peek() != is_expiring {
+                    session_expiring.set(is_expiring);
+                }
+
+                if let Err(error) = oauth2::time::sleep(SESSION_CHECK_INTERVAL).await {
+                    tracing::warn!("failed to watch session expiry: {error:?}");
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Renews the session with a `prompt=none` redirect, on web the page unloads and `login_with` handles the callback.
+    /// Started by the user from the expiry warning, so unsaved state isn't lost to an unexpected navigation.
+    pub fn renew_session(&self) {
+        let mut state = self.state;
+        let mut session_expiring = self.session_expiring;
+        let client = self.client();
+
+        spawn(async move {
+            // the session stays usable until its hard expiry, a failed renewal only leaves the warning up
+            if let Err(error) = client.renew_session_with_redirect().await {
+                tracing::warn!("failed to renew session: {error:?}");
+
+                if oauth2::error::ends_session(&error) {
+                    session_expiring.set(true);
+                }
+
+                return;
+            }
+
+            if client.is_authenticated.load(Ordering::Acquire) {
+                state.set(AuthState::Authenticated(client.user_profile().ok().flatten().map(Box::new)));
+            }
+        });
+    }
+
+    /// Follows the connectivity of the platform, resuming the queued refresh or login once back online.
+    pub fn watch_connectivity(&self) {
+        let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+
+        let callback = Rc::new(move |is_online| {
+            let _ = sender.unbounded_send(is_online);
+        });
+
+        if let Err(error) = self.client.read().watch_connectivity(callback) {
+            tracing::warn!("failed to watch connectivity: {error:?}");
+            return;
+        }
+
+        let auth = *self;
+        let mut online = self.online;
+
+        spawn(async move {
+            while let Some(is_online) = receiver.next().await {
+                online.set(is_online);
+
+                if is_online && auth.client.read().take_pending_refresh() {
+                    auth.resume();
+                }
+            }
+        });
+    }
+
+    fn resume(&self) {
+        if !matches!(self.state(), AuthState::Authenticated(_)) {
+            self.login();
+            return;
+        }
+
+        let auth = *self;
+
+        spawn(async move {
+            if let Err(error) = auth.get_token().await {
+                tracing::warn!("failed to resume the refresh: {error:?}");
+            }
+        });
+    }
+
+    pub fn client(&self) -> oauth2::azure::AuthorizationCodeFlowWithPKCE {
+        self.client.read().clone()
+    }
+
+    pub fn granted_scopes(&self) -> Vec<String> {
+        self.client.read().granted_scopes().unwrap_or_default()
+    }
+
+    pub fn login(&self) {
+        self.login_with(oauth2::params::AuthorizationOptions::default());
+    }
+
+    pub fn login_with(&self, options: oauth2::params::AuthorizationOptions) {
+        let mut state = self.state;
+        let mut session_expiring = self.session_expiring;
+        let client = self.client();
+
+        // the provider can't be reached, a cached session stays usable until connectivity returns
+        if !client.is_online() {
+            client.mark_refresh_pending();
+
+            let offline_state = if client.has_cached_token() {
+                AuthState::Authenticated(client.user_profile().ok().flatten().map(Box::new))
+            } else {
+                AuthState::Error("network unavailable".to_owned())
+            };
+
+            if *state.peek() != offline_state {
+                state.set(offline_state);
+            }
+
+            return;
+        }
+
+        state.set(AuthState::Authenticating);
+
+        if client.is_device_code_flow() {
+            spawn(async move {
+                if let Err(error) = Self::login_with_device_code(&client, state).await {
+                    tracing::error!("{error:?}");
+                    state.set(AuthState::Error(error.to_string()));
+                }
+            });
+
+            return;
+        }
+
+        spawn(async move {
+            if let Err(error) = client.login_with_redirect(&options).await {
+                // a prompt=none renewal refused by the provider, the cached session lasts until its hard expiry
+                if oauth2::error::ends_session(&error) {
+                    tracing::warn!("session renewal refused: {error:?}");
+
+                    if client.has_cached_token() {
+                        session_expiring.set(true);
+                        state.set(AuthState::Authenticated(client.user_profile().ok().flatten().map(Box::new)));
+                    } else {
+                        state.set(AuthState::Unauthenticated);
+                    }
+
+                    return;
+                }
+
+                tracing::error!("{error:?}");
+                state.set(AuthState::Error(error.to_string()));
+                return;
+            }
+
+            // login_with_redirect also succeeds when it just navigated away to the authorize endpoint
+            if !client.is_authenticated.load(Ordering::Acquire) {
+                return;
+            }
+
+            match client.user_profile() {
+                Ok(user_profile) => state.set(AuthState::Authenticated(user_profile.map(Box::new))),
+                Err(error) => state.set(AuthState::Error(error.to_string())),
+            }
+        });
+    }
+
+    async fn login_with_device_code(
+        client: &oauth2::azure::AuthorizationCodeFlowWithPKCE,
+        mut state: Signal<AuthState>,
+    ) -> anyhow::Result<()> {
+        let device_authorization = client.request_device_authorization().await?;
+        state.set(AuthState::DeviceAuthorization(Box::new(device_authorization.clone())));
+
+        client.poll_device_token(&device_authorization).await?;
+        state.set(AuthState::Authenticated(client.user_profile()?.map(Box::new)));
+
+        Ok(())
+    }
+
+    pub fn fetch_userinfo(&self) {
+        let mut state = self.state;
+        let client = self.client();
+
+        spawn(async move {
+            match client.fetch_userinfo().await {
+                Ok(user_profile) => state.set(AuthState::Authenticated(Some(Box::new(user_profile)))),
+                Err(error) => tracing::warn!("failed to fetch userinfo: {error:?}"),
+            }
+        });
+    }
+
+    pub fn logout(&self) {
+        let mut state = self.state;
+        let client = self.client();
+
+        spawn(async move {
+            if let Err(error) = client.logout().await {
+                tracing::error!("{error:?}");
+                state.set(AuthState::Error(error.to_string()));
+                return;
+            }
+
+            state.set(AuthState::Unauthenticated);
+        });
+    }
+
+    /// Current access token, refreshed when needed. Failures are left to the caller, the session only ends
+    /// when the provider rejects it, network and server failures keep it.
+    pub async fn get_token(&self) -> anyhow::Result<Option<oauth2::token::TokenRecord>> {
+        let mut state = self.state;
+
+        let token = self.client().acquire_token_silent().await;
+
+        if let Err(error) = &token
+            && oauth2::error::ends_session(error)
+        {
+            tracing::warn!("session ended: {error:?}");
+            state.set(AuthState::Unauthenticated);
+        }
+
+        token
+    }
+}
+
+pub fn use_auth() -> AuthContext {
+    use_context::<AuthContext>()
+}
@@ -0,0 +1,22 @@
+use crate::{
+    components::guard::{GuardComponent, Requirements},
+    router::Route,
+};
+use dioxus::prelude::*;
+use std::time::Duration;
+
+const ADMIN_REQUIREMENTS: Requirements = Requirements::new()
+    .with_roles(&["Admin"])
+    .with_scopes(&["access"])
+    .require_recent_auth(Duration::from_secs(15 * 60));
+
+#[component]
+pub fn AdminLayout() -> Element {
+    rsx! {
+        GuardComponent {
+            requirements: ADMIN_REQUIREMENTS,
+
+            Outlet::<Route> {}
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use dioxus_app::oauth2;
+
 mod app;
 mod components;
+mod hooks;
 mod layouts;
-mod oauth2;
 mod pages;
 mod router;
 
@@ -1,30 +1,71 @@
 use anyhow::anyhow;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 use dioxus::logger::tracing;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 use url::Url;
 use url::form_urlencoded;
-use web_sys::UrlSearchParams;
 
+use crate::oauth2::claims;
+use crate::oauth2::clock;
 use crate::oauth2::csrf;
+use crate::oauth2::device;
+use crate::oauth2::dpop;
+use crate::oauth2::error;
+use crate::oauth2::http;
+use crate::oauth2::jar;
+use crate::oauth2::jarm;
+use crate::oauth2::jwks;
 use crate::oauth2::oidc;
+use crate::oauth2::par;
+use crate::oauth2::params;
 use crate::oauth2::params::Params;
 use crate::oauth2::pkce;
+use crate::oauth2::platform;
+use crate::oauth2::retry;
 use crate::oauth2::storage;
+use crate::oauth2::time;
 use crate::oauth2::token;
+use crate::oauth2::transaction;
+
+const FORBIDDEN_PATH: &str = "/forbidden";
+
+#[derive(Debug, Default)]
+struct AuthorizationResponseParams {
+    code: Option<String>,
+    id_token: Option<String>,
+    state: Option<String>,
+    iss: Option<String>,
+    error: Option<error::ErrorResponse>,
+}
 
 #[derive(Default, Debug, Clone)]
 pub struct AuthorizationCodeFlowWithPKCE {
     pub is_authenticated: Arc<AtomicBool>,
+    refresh_pending: Arc<AtomicBool>,
 
     hybrid_flow: bool,
+    device_code_flow: bool,
+    jwt_response_mode: bool,
+    pushed_authorization_requests: bool,
+    dpop: Option<dpop::DPoP>,
+    request_object: Option<jar::RequestObject>,
+    platform: platform::PlatformRef,
+    http_client: http::HttpClientRef,
+    retry_policy: retry::RetryPolicy,
+    clock: clock::Clock,
+    refresh_token_max_age: Option<Duration>,
     persistence: storage::StorageType,
     oidc_url: &'static str,
     client_id: &'static str,
     scope: &'static str,
+    resources: Vec<&'static str>,
 }
 
 impl AuthorizationCodeFlowWithPKCE {
@@ -43,206 +84,492 @@ impl AuthorizationCodeFlowWithPKCE {
         self
     }
 
+    // https://datatracker.ietf.org/doc/html/rfc8707#section-2
+    pub fn with_resource(mut self, s: &'static str) -> Self {
+        self.resources.push(s);
+        self
+    }
+
     pub fn with_session_storage(mut self) -> Self {
         self.persistence = storage::StorageType::SessionStorage;
         self
     }
 
+    /// Keeps the oauth state in process memory, for builds without web storage.
+    pub fn with_memory_storage(mut self) -> Self {
+        self.persistence = storage::StorageType::Memory(storage::MemoryStore::default());
+        self
+    }
+
+    /// Keeps the oauth state in the secure store registered by the app shell, for mobile builds.
+    pub fn with_secure_storage(mut self) -> Self {
+        self.persistence = storage::StorageType::Secure;
+        self
+    }
+
+    /// Replaces the browser window as the user agent driving the redirect, e.g. with a loopback redirect on desktop.
+    pub fn with_platform(mut self, platform: impl platform::Platform + 'static) -> Self {
+        self.platform = platform::PlatformRef::new(platform);
+        self
+    }
+
+    /// Sends discovery, token and resource requests through the given transport instead of a default reqwest client.
+    pub fn with_http_client(mut self, http_client: impl http::HttpClient + 'static) -> Self {
+        self.http_client = http::HttpClientRef::new(http_client);
+        self
+    }
+
+    /// Overrides how often discovery and token requests are retried on network errors, 5xx and 429 responses.
+    pub fn with_retry_policy(mut self, retry_policy: retry::RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Tolerance when checking expiry and the auth_time, exp and nbf claims, tokens are refreshed this much early.
+    pub fn with_clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock.skew = clock_skew;
+        self
+    }
+
+    /// Lifetime assumed for refresh tokens when the provider doesn't announce one, the session ends with it.
+    pub fn with_refresh_token_max_age(mut self, refresh_token_max_age: Duration) -> Self {
+        self.refresh_token_max_age = Some(refresh_token_max_age);
+        self
+    }
+
     pub fn with_hybrid_flow(mut self) -> Self {
         self.hybrid_flow = true;
         self
     }
 
-    fn build_authorize_endpoint(&self, oidc_conf: &oidc::Configuration) -> anyhow::Result<String> {
-        let Some(window) = web_sys::window() else {
-            anyhow::bail!("window not available");
-        };
+    /// Signs in entering a user code on a second device instead of redirecting, e.g. for kiosks or desktop builds.
+    pub fn with_device_code_flow(mut self) -> Self {
+        self.device_code_flow = true;
+        self
+    }
 
-        let Ok(redirect_uri) = window.location().origin() else {
-            anyhow::bail!("location origin not available");
-        };
+    pub fn is_device_code_flow(&self) -> bool {
+        self.device_code_flow
+    }
+
+    /// Receives the authorization response as a jwt signed by the provider instead of plain parameters.
+    pub fn with_jwt_response_mode(mut self) -> Self {
+        self.jwt_response_mode = true;
+        self
+    }
+
+    /// Pushes the authorization parameters to the provider when it supports it, keeping them out of the browser url.
+    pub fn with_pushed_authorization_requests(mut self) -> Self {
+        self.pushed_authorization_requests = true;
+        self
+    }
+
+    /// Binds the tokens to a per-session key, every token request and authorized request carries a dpop proof.
+    /// The key lives in WebCrypto, so token requests fail on native builds.
+    pub fn with_dpop(mut self) -> Self {
+        self.dpop = Some(dpop::DPoP::new(self.clock.clone()));
+        self
+    }
+
+    /// Sends the authorization parameters inside a `request` object, pushed to the provider when PAR is enabled.
+    pub fn with_request_object(mut self, request_object: jar::RequestObject) -> Self {
+        self.request_object = Some(request_object);
+        self
+    }
+
+    /// Clock of the provider, calibrated with every token response.
+    pub fn clock(&self) -> &clock::Clock {
+        &self.clock
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.platform.is_online()
+    }
+
+    pub fn watch_connectivity(&self, callback: Rc<dyn Fn(bool)>) -> anyhow::Result<()> {
+        self.platform.watch_connectivity(callback)
+    }
+
+    /// Whether a session holds a token, even an expired one.
+    pub fn has_cached_token(&self) -> bool {
+        token::TokenRecord::retrieve_unchecked(&self.persistence, None)
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Queues a refresh or login until connectivity returns.
+    pub fn mark_refresh_pending(&self) {
+        self.refresh_pending.store(true, Ordering::Release);
+    }
+
+    pub fn take_pending_refresh(&self) -> bool {
+        self.refresh_pending.swap(false, Ordering::AcqRel)
+    }
+
+    async fn oidc_configuration(&self) -> anyhow::Result<oidc::Configuration> {
+        oidc::Configuration::from_remote(&*self.http_client, &self.retry_policy, self.oidc_url).await
+    }
+
+    async fn build_authorize_endpoint(
+        &self,
+        oidc_conf: &oidc::Configuration,
+        options: &params::AuthorizationOptions,
+    ) -> anyhow::Result<String> {
+        let redirect_uri = self.platform.redirect_uri()?;
 
         let csrf_nonce = csrf::Nonce::new();
-        csrf_nonce.persist(self.persistence)?;
+        csrf_nonce.persist(&self.persistence)?;
 
         let csrf_state = csrf::State::new();
-        csrf_state.persist(self.persistence)?;
+        csrf_state.persist(&self.persistence)?;
 
         let pkce_code_verifier = pkce::CodeVerifier::new();
-        pkce_code_verifier.persist(self.persistence)?;
+        pkce_code_verifier.persist(&self.persistence)?;
         let pkce_code_challenge = pkce::CodeChallenge::from(&pkce_code_verifier);
 
-        // microsoft encoding
-        let response_type = { if self.hybrid_flow { "code%20id_token" } else { "code" } };
-        let response_mode = { if self.hybrid_flow { "fragment" } else { "query" } };
+        let transaction = transaction::Transaction {
+            redirect_uri: Some(redirect_uri.clone()),
+            max_age: options.max_age.map(|max_age| max_age.as_secs() as i64),
+            issuer: Some(oidc_conf.issuer.clone()),
+        };
+        transaction.persist(&self.persistence)?;
+
+        let response_type = { if self.hybrid_flow { "code id_token" } else { "code" } };
+        let response_mode = {
+            match (self.hybrid_flow, self.jwt_response_mode) {
+                (true, true) => "fragment.jwt",
+                (true, false) => "fragment",
+                (false, true) => "query.jwt",
+                (false, false) => "query",
+            }
+        };
 
+        // max_age is validated against the auth_time of an id token, which is only issued for openid
         let scope = {
-            if self.hybrid_flow {
-                format!("openid%20{}", self.scope)
+            if self.hybrid_flow || options.max_age.is_some() {
+                format!("openid {}", self.scope)
             } else {
                 self.scope.to_owned()
             }
         };
 
         let mut params = vec![
-            (Params::ClientId.to_string(), self.client_id),
-            (Params::RedirectUri.to_string(), redirect_uri.as_str()),
-            (Params::ResponseMode.to_string(), response_mode),
-            (Params::State.to_string(), csrf_state.as_str()),
-            (Params::CodeChallenge.to_string(), pkce_code_challenge.as_str()),
-            (Params::CodeChallengeMethod.to_string(), "S256"),
+            (Params::ClientId.to_string(), self.client_id.to_owned()),
+            (Params::RedirectUri.to_string(), redirect_uri),
+            (Params::ResponseMode.to_string(), response_mode.to_owned()),
+            (Params::State.to_string(), csrf_state.as_str().to_owned()),
+            (Params::CodeChallenge.to_string(), pkce_code_challenge.as_str().to_owned()),
+            (Params::CodeChallengeMethod.to_string(), "S256".to_owned()),
         ];
 
         if self.hybrid_flow {
-            params.push((Params::Nonce.to_string(), csrf_nonce.as_str()));
+            params.push((Params::Nonce.to_string(), csrf_nonce.as_str().to_owned()));
+        }
+
+        params.extend(
+            self.resources
+                .iter()
+                .map(|resource| (Params::Resource.to_string(), resource.to_string())),
+        );
+
+        params.extend(options.to_params());
+
+        let request_object = self.request_object.as_ref().filter(|request_object| {
+            let is_supported = request_object.is_supported(&oidc_conf.request_object_signing_alg_values_supported);
+
+            if !is_supported {
+                tracing::warn!("unsigned request objects not accepted by the provider, falling back to query parameters");
+            }
+
+            is_supported
+        });
+
+        // https://datatracker.ietf.org/doc/html/rfc9101#section-5
+        if let Some(request_object) = request_object {
+            let mut claims = params;
+            claims.push((Params::Scope.to_string(), scope.clone()));
+            claims.push((Params::ResponseType.to_string(), response_type.to_owned()));
+
+            let request = request_object.encode(&claims, self.client_id, &oidc_conf.issuer, &self.clock)?;
+
+            // scope and response_type are still sent outside of the request object as openid requires
+            params = vec![
+                (Params::ClientId.to_string(), self.client_id.to_owned()),
+                (Params::Request.to_string(), request),
+            ];
         }
 
         let mut base_url = Url::parse(&oidc_conf.authorization_endpoint)?;
 
+        // https://datatracker.ietf.org/doc/html/rfc9126#section-4
+        if self.pushed_authorization_requests {
+            if let Some(par_endpoint) = &oidc_conf.pushed_authorization_request_endpoint {
+                params.push((Params::Scope.to_string(), scope));
+                params.push((Params::ResponseType.to_string(), response_type.to_owned()));
+
+                let par_response = par::PushedAuthorizationResponse::request(&*self.http_client, par_endpoint, &params).await?;
+
+                base_url
+                    .query_pairs_mut()
+                    .append_pair(&Params::ClientId.to_string(), self.client_id)
+                    .append_pair(&Params::RequestUri.to_string(), &par_response.request_uri);
+
+                return Ok(base_url.to_string());
+            }
+
+            tracing::warn!("pushed authorization request endpoint not available, falling back to query parameters");
+        }
+
         for (key, value) in params.iter() {
             base_url.query_pairs_mut().append_pair(key, value);
         }
 
+        // microsoft encoding
         Ok(format!(
-            "{base}{scope}{response_type}",
+            "{base}&{scope_key}={scope}&{response_type_key}={response_type}",
             base = base_url.as_str(),
-            scope = format!("&{}={}", Params::Scope, scope),
-            response_type = format!("&{}={}", Params::ResponseType, response_type)
+            scope_key = Params::Scope,
+            scope = scope.replace(' ', "%20"),
+            response_type_key = Params::ResponseType,
+            response_type = response_type.replace(' ', "%20"),
         ))
     }
 
     async fn request_authorization_token(
         &self,
         oidc_conf: &oidc::Configuration,
+        transaction: &transaction::Transaction,
         code: &str,
         state: &str,
-    ) -> anyhow::Result<token::TokenResponse> {
-        let Some(window) = web_sys::window() else {
-            anyhow::bail!("window not available");
+    ) -> anyhow::Result<token::TokenRecord> {
+        // https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.3
+        let Some(redirect_uri) = transaction.redirect_uri.as_deref() else {
+            anyhow::bail!("redirect uri of the authorization request not available");
         };
 
-        let Ok(redirect_uri) = window.location().origin() else {
-            anyhow::bail!("location origin not available");
-        };
+        let code_verifier = pkce::CodeVerifier::retrieve(&self.persistence)?;
 
-        let code_verifier = pkce::CodeVerifier::retrieve(self.persistence)?;
-
-        let params_raw = &[
+        let mut params = vec![
             (Params::ClientId.to_string(), self.client_id),
             (Params::Scope.to_string(), self.scope),
             (Params::Code.to_string(), code),
-            (Params::RedirectUri.to_string(), redirect_uri.as_str()),
+            (Params::RedirectUri.to_string(), redirect_uri),
             (Params::GrantType.to_string(), "authorization_code"),
             (Params::State.to_string(), state),
             (Params::CodeVerifier.to_string(), code_verifier.as_str()),
         ];
 
-        let mut params = HashMap::new();
-        for (k, v) in params_raw {
-            params.insert(k, v);
-        }
-
-        let client = reqwest::Client::new();
+        params.extend(
+            self.resources
+                .iter()
+                .map(|resource| (Params::Resource.to_string(), *resource)),
+        );
 
-        let response = client
-            .post(&oidc_conf.token_endpoint)
-            .form(&params)
-            .send()
+        let response = self
+            .send_token_request(&oidc_conf.token_endpoint, &params)
             .await?
-            .json::<token::TokenResponse>()
-            .await?;
+            .error_for_status()?;
 
-        Ok(response)
+        let mut token_record = self.parse_token_response(oidc_conf, &response)?;
+        token_record.redirect_uri = Some(redirect_uri.to_owned());
+        self.limit_session(&mut token_record);
+
+        Ok(token_record)
     }
 
-    fn clear_auth_params_from_url() -> anyhow::Result<()> {
-        if let Some(window) = web_sys::window() {
-            let location = window.location();
+    async fn send_token_request<K: AsRef<str>, V: AsRef<str>>(
+        &self,
+        token_endpoint: &str,
+        params: &[(K, V)],
+    ) -> anyhow::Result<http::Response> {
+        retry::send(&self.retry_policy, || async {
+            let request = http::Request::post_form(token_endpoint, params);
+
+            let Some(dpop) = &self.dpop else {
+                return self.http_client.send(request).await;
+            };
 
-            let path = location
-                .pathname()
-                .map_err(|err| anyhow!("failed to get pathname: {:?}", err))?;
+            dpop.send(&*self.http_client, request, None).await
+        })
+        .await
+    }
 
-            if let Some(history) = window.history().ok() {
-                history
-                    .replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&path))
-                    .map_err(|err| anyhow!("failed to replace state: {:?}", err))?;
-            }
+    fn parse_token_response(
+        &self,
+        oidc_conf: &oidc::Configuration,
+        response: &http::Response,
+    ) -> anyhow::Result<token::TokenRecord> {
+        let token_response = response.json::<token::TokenResponse>()?;
+
+        self.clock.calibrate_from_token_response(response, &token_response);
+
+        let mut token_record = token::TokenRecord::new(&oidc_conf.issuer, token_response, self.clock.now());
+        self.bind_to_dpop_key(&mut token_record);
+
+        Ok(token_record)
+    }
+
+    fn limit_session(&self, token_record: &mut token::TokenRecord) {
+        if token_record.refresh_expires_at.is_none()
+            && token_record.refresh_token.is_some()
+            && let Some(refresh_token_max_age) = self.refresh_token_max_age
+        {
+            token_record.refresh_expires_at = Some(token_record.issued_at + refresh_token_max_age.as_secs() as i64);
         }
+    }
 
-        Ok(())
+    fn bind_to_dpop_key(&self, token_record: &mut token::TokenRecord) {
+        if let Some(dpop) = &self.dpop
+            && token_record.token_type.eq_ignore_ascii_case(dpop::DPOP_TOKEN_TYPE)
+        {
+            token_record.dpop_jkt = dpop.thumbprint();
+        }
     }
 
-    fn extract_auth_params_from_url(&self) -> anyhow::Result<(Option<String>, Option<String>, Option<String>)> {
-        let Some(window) = web_sys::window() else {
-            anyhow::bail!("window not available");
+    fn is_bound_to_current_dpop_key(&self, token_record: &token::TokenRecord) -> bool {
+        let Some(dpop_jkt) = &token_record.dpop_jkt else {
+            return true;
+        };
+
+        self.dpop.as_ref().and_then(|dpop| dpop.thumbprint()).as_ref() == Some(dpop_jkt)
+    }
+
+    async fn extract_auth_params_from_url(
+        &self,
+        oidc_conf: &oidc::Configuration,
+        url: Option<Url>,
+    ) -> anyhow::Result<AuthorizationResponseParams> {
+        let Some(url) = url else {
+            return Ok(AuthorizationResponseParams::default());
         };
 
         // https://openid.net/specs/oauth-v2-multiple-response-types-1_0.html#Combinations
-        if self.hybrid_flow {
-            let hash = window
-                .location()
-                .hash()
-                .map_err(|_| anyhow::anyhow!("location hash not available"))?;
+        let params = if self.hybrid_flow { url.fragment() } else { url.query() };
+
+        let structured_params: HashMap<String, String> = form_urlencoded::parse(params.unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect();
+
+        // https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.2.1
+        let error = structured_params.get("error").map(|error| error::ErrorResponse {
+            error: error.clone(),
+            error_description: structured_params.get("error_description").cloned(),
+        });
+
+        // providers without jwt response modes answer the request with a plain error
+        if self.jwt_response_mode && error.is_none() {
+            return self
+                .verify_authorization_response(oidc_conf, structured_params.get("response"))
+                .await;
+        }
 
-            if hash.is_empty() {
-                return Ok((None, None, None));
-            }
+        Ok(AuthorizationResponseParams {
+            code: structured_params.get("code").cloned(),
+            id_token: structured_params.get("id_token").cloned(),
+            state: structured_params.get("state").cloned(),
+            iss: structured_params.get("iss").cloned(),
+            error,
+        })
+    }
 
-            let hash_params = &hash[1..];
+    // https://openid.net/specs/oauth-v2-jarm.html#section-2.4
+    async fn verify_authorization_response(
+        &self,
+        oidc_conf: &oidc::Configuration,
+        response: Option<&String>,
+    ) -> anyhow::Result<AuthorizationResponseParams> {
+        let Some(response) = response else {
+            return Ok(AuthorizationResponseParams::default());
+        };
+
+        let Some(jwks_uri) = &oidc_conf.jwks_uri else {
+            anyhow::bail!("jwks uri not available");
+        };
 
-            let structured_hash_params: HashMap<String, String> = form_urlencoded::parse(hash_params.as_bytes())
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect();
+        let jwks = jwks::JsonWebKeySet::from_remote(&*self.http_client, &self.retry_policy, jwks_uri).await?;
 
-            return Ok((
-                structured_hash_params.get("code").cloned(),
-                structured_hash_params.get("id_token").cloned(),
-                structured_hash_params.get("state").cloned(),
-            ));
-        } else {
-            let Ok(search) = window.location().search() else {
-                anyhow::bail!("location search not available");
+        let authorization_response =
+            match jarm::AuthorizationResponse::verify(response, &jwks, &oidc_conf.issuer, self.client_id, &self.clock) {
+                Ok(authorization_response) => authorization_response,
+                Err(error) => {
+                    self.clear_all()?;
+                    return Err(error);
+                }
             };
 
-            let url_search_params = UrlSearchParams::new_with_str(&search).map_err(|err| anyhow!("{err:?}"))?;
+        Ok(AuthorizationResponseParams {
+            code: authorization_response.code,
+            id_token: authorization_response.id_token,
+            state: authorization_response.state,
+            iss: authorization_response.iss,
+            error: None,
+        })
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation
+    async fn verify_id_token(&self, oidc_conf: &oidc::Configuration, id_token: &str) -> anyhow::Result<token::IdToken> {
+        let Some(jwks_uri) = &oidc_conf.jwks_uri else {
+            anyhow::bail!("jwks uri not available");
+        };
 
-            return Ok((url_search_params.get("code"), None, url_search_params.get("state")));
-        }
+        let jwks = jwks::JsonWebKeySet::from_remote(&*self.http_client, &self.retry_policy, jwks_uri).await?;
+
+        jwks.verify(id_token, &oidc_conf.issuer, self.client_id, &self.clock)
     }
 
     fn clear_all(&self) -> anyhow::Result<()> {
-        Self::clear_auth_params_from_url()?;
-        csrf::State::unpersist(self.persistence)?;
-        pkce::CodeVerifier::unpersist(self.persistence)?;
+        self.platform.clean_history()?;
+        csrf::State::unpersist(&self.persistence)?;
+        pkce::CodeVerifier::unpersist(&self.persistence)?;
+        transaction::Transaction::unpersist(&self.persistence)?;
 
         if self.hybrid_flow {
-            csrf::Nonce::unpersist(self.persistence)?;
+            csrf::Nonce::unpersist(&self.persistence)?;
         }
 
         Ok(())
     }
 
-    pub async fn login_with_redirect(&self) -> anyhow::Result<()> {
-        let Some(window) = web_sys::window() else {
-            anyhow::bail!("window not available");
-        };
+    pub async fn login_with_redirect(&self, options: &params::AuthorizationOptions) -> anyhow::Result<()> {
+        let oidc_conf = self.oidc_configuration().await?;
 
-        let oidc_conf = oidc::Configuration::from_remote(self.oidc_url).await?;
-
-        let (code, id_token, state) = self.extract_auth_params_from_url()?;
+        let mut response = self
+            .extract_auth_params_from_url(&oidc_conf, self.platform.current_url()?)
+            .await?;
 
-        let Some(code) = code else {
-            let endpoint_url = self.build_authorize_endpoint(&oidc_conf)?;
+        // an error response ends the login, e.g. the provider refusing a prompt=none renewal
+        if response.code.is_none() && response.error.is_none() {
+            let endpoint_url = self.build_authorize_endpoint(&oidc_conf, options).await?;
 
-            window
-                .location()
-                .set_href(endpoint_url.as_str())
-                .map_err(|err| anyhow!("{err:?}"))?;
+            self.platform.navigate(&endpoint_url)?;
 
             tracing::debug!("{endpoint_url}");
 
-            return Ok(());
+            // browsers unload the page here, native apps receive the response while still running
+            let Some(callback_url) = self.platform.wait_for_callback().await? else {
+                return Ok(());
+            };
+
+            response = self.extract_auth_params_from_url(&oidc_conf, Some(callback_url)).await?;
+        }
+
+        let AuthorizationResponseParams {
+            code,
+            id_token,
+            state,
+            iss,
+            error,
+        } = response;
+
+        if let Some(error) = error {
+            self.clear_all()?;
+            return Err(error.into());
+        }
+
+        let Some(code) = code else {
+            self.clear_all()?;
+            anyhow::bail!("param code not available");
         };
 
         let Some(state) = state else {
@@ -250,45 +577,112 @@ impl AuthorizationCodeFlowWithPKCE {
             anyhow::bail!("param state not available");
         };
 
-        if !csrf::State::exists_and_matches_raw(self.persistence, &state) {
+        if !csrf::State::exists_and_matches_raw(&self.persistence, &state) {
             tracing::error!("invalid state");
 
             self.clear_all()?;
 
-            window
-                .location()
-                .set_pathname("/forbidden")
-                .map_err(|err| anyhow!("{err:?}"))?;
+            self.platform.navigate(FORBIDDEN_PATH)?;
+
+            return Ok(());
+        }
+
+        let transaction = transaction::Transaction::retrieve(&self.persistence)?;
+
+        // https://datatracker.ietf.org/doc/html/rfc9207#section-2.4
+        let is_issuer_valid = match (&iss, &transaction.issuer) {
+            (Some(iss), Some(issuer)) => iss == issuer,
+            (None, _) => !oidc_conf.authorization_response_iss_parameter_supported,
+            (Some(_), None) => true,
+        };
+
+        if !is_issuer_valid {
+            tracing::error!("invalid issuer {iss:?}");
+
+            self.clear_all()?;
+
+            self.platform.navigate(FORBIDDEN_PATH)?;
 
             return Ok(());
         }
 
-        let mut future_id_token: Option<String> = None;
+        let mut front_channel_id_token = None;
 
         if self.hybrid_flow {
             let Some(id_token) = id_token else {
+                self.clear_all()?;
                 anyhow::bail!("param id_token not available");
             };
 
-            let id_token_parts = token::IdToken::from_str(&id_token).map_err(|err| anyhow!("{err:?}"))?;
+            let id_token_claims = match self.verify_id_token(&oidc_conf, &id_token).await {
+                Ok(id_token_claims) => id_token_claims,
+                Err(error) => {
+                    self.clear_all()?;
+                    return Err(error);
+                }
+            };
 
-            if !csrf::Nonce::exists_and_matches_raw(self.persistence, &id_token_parts.nonce) {
+            if !csrf::Nonce::exists_and_matches_raw(&self.persistence, &id_token_claims.nonce) {
                 self.clear_all()?;
 
-                window
-                    .location()
-                    .set_pathname("/forbidden")
-                    .map_err(|err| anyhow!("{err:?}"))?;
+                self.platform.navigate(FORBIDDEN_PATH)?;
 
                 return Ok(());
             }
 
-            future_id_token = Some(id_token);
+            front_channel_id_token = Some((id_token, id_token_claims));
+        }
+
+        let mut token_record = self
+            .request_authorization_token(&oidc_conf, &transaction, &code, &state)
+            .await?;
+
+        // the id token of the token endpoint is kept, the front channel one only when the response has none
+        let id_token_claims = match token_record.id_token.as_deref() {
+            Some(id_token) => {
+                let id_token_claims = match self.verify_id_token(&oidc_conf, id_token).await {
+                    Ok(id_token_claims) => id_token_claims,
+                    Err(error) => {
+                        self.clear_all()?;
+                        return Err(error);
+                    }
+                };
+
+                // https://openid.net/specs/openid-connect-core-1_0.html#HybridTokenValidation2
+                if let Some((_, front_channel_claims)) = &front_channel_id_token
+                    && front_channel_claims.sub != id_token_claims.sub
+                {
+                    self.clear_all()?;
+                    anyhow::bail!("id tokens of the authorization and token responses are for different subjects");
+                }
+
+                Some(id_token_claims)
+            }
+            None => front_channel_id_token.map(|(id_token, id_token_claims)| {
+                token_record.id_token = Some(id_token);
+                id_token_claims
+            }),
+        };
+
+        // https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation
+        if let Some(max_age) = transaction.max_age {
+            let auth_time = id_token_claims.and_then(|id_token_claims| id_token_claims.auth_time);
+
+            let Some(auth_time) = auth_time else {
+                self.clear_all()?;
+                anyhow::bail!("claim auth_time not available to validate max_age");
+            };
+
+            if self.clock.now() > auth_time + max_age + self.clock.skew_secs() {
+                self.clear_all()?;
+                anyhow::bail!("authentication is older than the requested max_age");
+            }
         }
 
-        let mut token_response = self.request_authorization_token(&oidc_conf, &code, &state).await?;
-        token_response.id_token = future_id_token;
-        token_response.persist(self.persistence)?;
+        // tokens of resources and exchanges belong to the previous grant, possibly of another user
+        token::TokenRecord::unpersist_all(&self.persistence)?;
+        token_record.persist(&self.persistence, None)?;
+        claims::UserProfile::unpersist_userinfo(&self.persistence)?;
 
         self.clear_all()?;
 
@@ -297,57 +691,687 @@ impl AuthorizationCodeFlowWithPKCE {
         Ok(())
     }
 
-    pub async fn acquire_token_silent(&self) -> anyhow::Result<Option<token::TokenResponse>> {
-        let Some(window) = web_sys::window() else {
-            anyhow::bail!("window not available");
+    // https://datatracker.ietf.org/doc/html/rfc8628#section-3.1
+    pub async fn request_device_authorization(&self) -> anyhow::Result<device::DeviceAuthorizationResponse> {
+        let oidc_conf = self.oidc_configuration().await?;
+
+        let Some(device_authorization_endpoint) = &oidc_conf.device_authorization_endpoint else {
+            anyhow::bail!("device authorization endpoint not available");
+        };
+
+        let scope = format!("openid {}", self.scope);
+
+        let mut params = vec![
+            (Params::ClientId.to_string(), self.client_id),
+            (Params::Scope.to_string(), scope.as_str()),
+        ];
+
+        params.extend(
+            self.resources
+                .iter()
+                .map(|resource| (Params::Resource.to_string(), *resource)),
+        );
+
+        let response = self
+            .http_client
+            .send(http::Request::post_form(device_authorization_endpoint, &params))
+            .await?
+            .error_for_status()?
+            .json::<device::DeviceAuthorizationResponse>()?;
+
+        Ok(response)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8628#section-3.4
+    pub async fn poll_device_token(
+        &self,
+        device_authorization: &device::DeviceAuthorizationResponse,
+    ) -> anyhow::Result<token::TokenRecord> {
+        let oidc_conf = self.oidc_configuration().await?;
+
+        let expires_at = chrono::Utc::now().timestamp() + device_authorization.expires_in;
+        let mut interval = device_authorization.interval;
+
+        let params = [
+            (Params::ClientId.to_string(), self.client_id),
+            (Params::GrantType.to_string(), device::DEVICE_CODE_GRANT_TYPE),
+            (Params::DeviceCode.to_string(), device_authorization.device_code.as_str()),
+        ];
+
+        loop {
+            time::sleep(Duration::from_secs(interval)).await?;
+
+            if chrono::Utc::now().timestamp() >= expires_at {
+                anyhow::bail!("device code expired");
+            }
+
+            let response = self.send_token_request(&oidc_conf.token_endpoint, &params).await?;
+
+            if response.status.is_success() {
+                let mut token_record = self.parse_token_response(&oidc_conf, &response)?;
+
+                if let Some(id_token) = &token_record.id_token {
+                    self.verify_id_token(&oidc_conf, id_token).await?;
+                }
+
+                self.limit_session(&mut token_record);
+                token::TokenRecord::unpersist_all(&self.persistence)?;
+                token_record.persist(&self.persistence, None)?;
+                claims::UserProfile::unpersist_userinfo(&self.persistence)?;
+
+                self.is_authenticated.store(true, Ordering::Release);
+
+                return Ok(token_record);
+            }
+
+            let outcome = response.json::<device::DeviceTokenError>()?.into_outcome()?;
+            interval = outcome.next_interval(interval);
+        }
+    }
+
+    /// Starts an interactive login when no token can be acquired silently,
+    /// device code logins are driven by the caller as they need the user code displayed.
+    async fn login_required(&self) -> anyhow::Result<()> {
+        if self.device_code_flow {
+            self.is_authenticated.store(false, Ordering::Release);
+            return Ok(());
+        }
+
+        self.login_with_redirect(&params::AuthorizationOptions::default()).await
+    }
+
+    /// Claims of the id token verified during the last login, available when `openid` is in scope,
+    /// which the hybrid and device code flows always request.
+    pub fn user_profile(&self) -> anyhow::Result<Option<claims::UserProfile>> {
+        let Some(token_record) = token::TokenRecord::retrieve_unchecked(&self.persistence, None)? else {
+            return Ok(None);
+        };
+
+        let Some(id_token) = token_record.id_token else {
+            return Ok(None);
+        };
+
+        let mut user_profile = claims::UserProfile::from_str(&id_token).map_err(|err| anyhow!("{err:?}"))?;
+
+        if let Some(userinfo) = claims::UserProfile::retrieve_userinfo(&self.persistence)? {
+            user_profile.merge(userinfo);
+        }
+
+        Ok(Some(user_profile))
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#UserInfo
+    pub async fn fetch_userinfo(&self) -> anyhow::Result<claims::UserProfile> {
+        let Some(mut user_profile) = self.user_profile()? else {
+            anyhow::bail!("id token not available");
+        };
+
+        if claims::UserProfile::retrieve_userinfo(&self.persistence)?.is_some() {
+            return Ok(user_profile);
+        }
+
+        let oidc_conf = self.oidc_configuration().await?;
+
+        let Some(userinfo_endpoint) = oidc_conf.userinfo_endpoint else {
+            anyhow::bail!("userinfo endpoint not available");
+        };
+
+        let userinfo = self
+            .send_authorized(http::Request::get(&userinfo_endpoint))
+            .await?
+            .error_for_status()?
+            .json::<claims::UserProfile>()?;
+
+        // https://openid.net/specs/openid-connect-core-1_0.html#UserInfoResponse
+        if userinfo.sub != user_profile.sub {
+            anyhow::bail!("userinfo sub does not match id token sub");
+        }
+
+        userinfo.persist_userinfo(&self.persistence)?;
+        user_profile.merge(userinfo);
+
+        Ok(user_profile)
+    }
+
+    /// Sends a request authorized with the current access token, signing a dpop proof when the token is bound to a key.
+    pub async fn send_authorized(&self, request: http::Request) -> anyhow::Result<http::Response> {
+        let Some(token_record) = self.acquire_token_silent().await? else {
+            anyhow::bail!("access token not available");
         };
 
-        if let Some(token_result) = token::TokenResponse::retrieve(self.persistence)? {
+        let dpop = self
+            .dpop
+            .as_ref()
+            .filter(|_| token_record.token_type.eq_ignore_ascii_case(dpop::DPOP_TOKEN_TYPE));
+
+        let Some(dpop) = dpop else {
+            let request = request.with_header(
+                reqwest::header::AUTHORIZATION.as_str(),
+                &format!("Bearer {}", token_record.access_token),
+            );
+
+            return self.http_client.send(request).await;
+        };
+
+        // https://datatracker.ietf.org/doc/html/rfc9449#section-7.1
+        let request = request.with_header(
+            reqwest::header::AUTHORIZATION.as_str(),
+            &format!("DPoP {}", token_record.access_token),
+        );
+
+        dpop.send(&*self.http_client, request, Some(&token_record.access_token)).await
+    }
+
+    /// Hard expiry of the session as unix time on the provider clock, an interactive login is needed past it.
+    pub fn session_expires_at(&self) -> anyhow::Result<Option<i64>> {
+        Ok(token::TokenRecord::retrieve_unchecked(&self.persistence, None)?
+            .and_then(|token_record| token_record.refresh_expires_at))
+    }
+
+    /// Renews the session before the refresh token dies with a `prompt=none` login, which only succeeds while the user
+    /// is still signed in at the provider. This is a redirect like any login: on web the page unloads and the outcome
+    /// arrives on the callback, where a refusal such as `login_required` is returned as an `error::ErrorResponse`.
+    pub async fn renew_session_with_redirect(&self) -> anyhow::Result<()> {
+        let mut options = params::AuthorizationOptions::default().with_prompt(params::Prompt::None);
+
+        if let Some(login_hint) = self.user_profile()?.and_then(|user_profile| user_profile.preferred_username) {
+            options = options.with_login_hint(login_hint);
+        }
+
+        self.login_with_redirect(&options).await
+    }
+
+    pub fn granted_scopes(&self) -> anyhow::Result<Vec<String>> {
+        let Some(token_record) = token::TokenRecord::retrieve_unchecked(&self.persistence, None)? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(token_record.scopes)
+    }
+
+    pub async fn logout(&self) -> anyhow::Result<()> {
+        let token_record = token::TokenRecord::retrieve_unchecked(&self.persistence, None)?;
+
+        token::TokenRecord::unpersist_all(&self.persistence)?;
+
+        claims::UserProfile::unpersist_userinfo(&self.persistence)?;
+
+        self.is_authenticated.store(false, Ordering::Release);
+
+        let oidc_conf = self.oidc_configuration().await?;
+
+        // https://openid.net/specs/openid-connect-rpinitiated-1_0.html#RPLogout
+        let Some(end_session_endpoint) = oidc_conf.end_session_endpoint else {
+            return Ok(());
+        };
+
+        let redirect_uri = self.platform.origin()?;
+
+        let mut end_session_url = Url::parse(&end_session_endpoint)?;
+
+        end_session_url
+            .query_pairs_mut()
+            .append_pair(&Params::ClientId.to_string(), self.client_id)
+            .append_pair(&Params::PostLogoutRedirectUri.to_string(), &redirect_uri);
+
+        if let Some(id_token) = token_record.and_then(|token_record| token_record.id_token) {
+            end_session_url
+                .query_pairs_mut()
+                .append_pair(&Params::IdTokenHint.to_string(), &id_token);
+        }
+
+        self.platform.navigate(end_session_url.as_str())?;
+
+        Ok(())
+    }
+
+    /// Exchanges a token of the signed in user for a downscoped or delegated one,
+    /// cached by subject token, audience, scope and requested token type until the next login.
+    // https://datatracker.ietf.org/doc/html/rfc8693#section-2.1
+    pub async fn exchange_token(
+        &self,
+        subject_token: &str,
+        requested_token_type: Option<&str>,
+        audience: Option<&str>,
+        scope: Option<&str>,
+    ) -> anyhow::Result<token::TokenRecord> {
+        // every parameter shaping the issued token is part of the key, encoded so values can't collide,
+        // the subject token only by its hash to keep it out of the storage keys
+        let cache_key = form_urlencoded::Serializer::new(String::from("exchange:"))
+            .append_pair(
+                &Params::SubjectToken.to_string(),
+                &URL_SAFE_NO_PAD.encode(Sha256::digest(subject_token.as_bytes())),
+            )
+            .append_pair(&Params::Audience.to_string(), audience.unwrap_or_default())
+            .append_pair(&Params::Scope.to_string(), scope.unwrap_or_default())
+            .append_pair(
+                &Params::RequestedTokenType.to_string(),
+                requested_token_type.unwrap_or_default(),
+            )
+            .finish();
+
+        if let Some(token_record) = token::TokenRecord::retrieve(&self.persistence, Some(&cache_key), &self.clock)?
+            && self.is_bound_to_current_dpop_key(&token_record)
+        {
+            return Ok(token_record);
+        }
+
+        let mut params = vec![
+            (Params::ClientId.to_string(), self.client_id),
+            (
+                Params::GrantType.to_string(),
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            ),
+            (Params::SubjectToken.to_string(), subject_token),
+            (Params::SubjectTokenType.to_string(), token::ACCESS_TOKEN_TYPE),
+        ];
+
+        if let Some(requested_token_type) = requested_token_type {
+            params.push((Params::RequestedTokenType.to_string(), requested_token_type));
+        }
+
+        if let Some(audience) = audience {
+            params.push((Params::Audience.to_string(), audience));
+        }
+
+        if let Some(scope) = scope {
+            params.push((Params::Scope.to_string(), scope));
+        }
+
+        let oidc_conf = self.oidc_configuration().await?;
+
+        let response = self
+            .send_token_request(&oidc_conf.token_endpoint, &params)
+            .await?
+            .error_for_status()?;
+
+        let token_record = self.parse_token_response(&oidc_conf, &response)?;
+        token_record.persist(&self.persistence, Some(&cache_key))?;
+
+        Ok(token_record)
+    }
+
+    pub async fn acquire_token_silent(&self) -> anyhow::Result<Option<token::TokenRecord>> {
+        self.acquire_token_silent_for_resource(None).await
+    }
+
+    /// Acquires an audience-restricted token for one of the resources configured with `with_resource`,
+    /// refreshing with the refresh token of the login grant.
+    pub async fn acquire_token_silent_for(&self, resource: &str) -> anyhow::Result<Option<token::TokenRecord>> {
+        if !self.resources.contains(&resource) {
+            anyhow::bail!("resource {resource} not requested at login");
+        }
+
+        self.acquire_token_silent_for_resource(Some(resource)).await
+    }
+
+    async fn acquire_token_silent_for_resource(&self, resource: Option<&str>) -> anyhow::Result<Option<token::TokenRecord>> {
+        // dpop keys only live in memory, tokens bound to the key of a previous page load are unusable
+        if let Some(token_record) = token::TokenRecord::retrieve_unchecked(&self.persistence, resource)?
+            && !self.is_bound_to_current_dpop_key(&token_record)
+        {
+            token_record.unpersist(&self.persistence, resource)?;
+        }
+
+        if let Some(token_result) = token::TokenRecord::retrieve(&self.persistence, resource, &self.clock)? {
             return Ok(Some(token_result));
         }
 
-        if let Some(token_response) = token::TokenResponse::retrieve_unchecked(self.persistence)? {
-            let Ok(redirect_uri) = window.location().origin() else {
-                anyhow::bail!("location origin not available");
-            };
+        // refreshing or redirecting while offline can only fail, the expired token keeps the app usable meanwhile
+        if !self.platform.is_online() {
+            self.mark_refresh_pending();
 
-            let client = reqwest::Client::new();
+            return Ok(
+                token::TokenRecord::retrieve_unchecked(&self.persistence, resource)?.map(|token_record| token::TokenRecord {
+                    is_stale: true,
+                    ..token_record
+                }),
+            );
+        }
+
+        // only the record of the login grant keeps the refresh token, it is valid for every resource requested at login
+        let grant_record = token::TokenRecord::retrieve_unchecked(&self.persistence, None)?
+            .filter(|token_record| self.is_bound_to_current_dpop_key(token_record));
+
+        // past the hard expiry of the session only an interactive login helps
+        if let Some(grant_record) = grant_record
+            && let Some(refresh_token) = grant_record.refresh_token.as_deref()
+            && !grant_record.is_refresh_expired(&self.clock)
+        {
+            // device code grants have no redirect uri of their own
+            let redirect_uri = match &grant_record.redirect_uri {
+                Some(redirect_uri) => redirect_uri.clone(),
+                None => self.platform.origin()?,
+            };
 
-            let params_raw = &[
+            let mut params = vec![
                 (Params::ClientId.to_string(), self.client_id),
                 (Params::Scope.to_string(), self.scope),
-                (Params::RefreshToken.to_string(), &token_response.refresh_token),
+                (Params::RefreshToken.to_string(), refresh_token),
                 (Params::RedirectUri.to_string(), redirect_uri.as_str()),
                 (Params::GrantType.to_string(), "refresh_token"),
             ];
 
-            let mut params = HashMap::new();
-            for (k, v) in params_raw {
-                params.insert(k, v);
+            match resource {
+                Some(resource) => params.push((Params::Resource.to_string(), resource)),
+                None => params.extend(
+                    self.resources
+                        .iter()
+                        .map(|resource| (Params::Resource.to_string(), *resource)),
+                ),
             }
 
-            let oidc_conf = oidc::Configuration::from_remote(self.oidc_url).await?;
+            let oidc_conf = self.oidc_configuration().await?;
 
-            let response = client.post(&oidc_conf.token_endpoint).form(&params).send().await?;
+            // network errors and unavailable servers keep the session, only rejected grants end it
+            let response = self.send_token_request(&oidc_conf.token_endpoint, &params).await?;
+
+            if response.is_transient() {
+                anyhow::bail!("token endpoint unavailable, status {}", response.status);
+            }
 
             match response.error_for_status() {
                 Ok(out) => {
-                    let token_response = out.json::<token::TokenResponse>().await?;
-                    tracing::debug!("token_response {token_response:?}");
-                    token_response.persist(self.persistence)?;
-                    return Ok(Some(token_response));
+                    let mut refreshed_token_record = self.parse_token_response(&oidc_conf, &out)?;
+
+                    // https://openid.net/specs/openid-connect-core-1_0.html#RefreshTokenResponse
+                    if let Some(id_token) = &refreshed_token_record.id_token {
+                        let id_token_claims = self.verify_id_token(&oidc_conf, id_token).await?;
+
+                        let previous_claims = grant_record
+                            .id_token
+                            .as_deref()
+                            .and_then(|id_token| token::IdToken::from_str(id_token).ok());
+
+                        if previous_claims.is_some_and(|previous_claims| previous_claims.sub != id_token_claims.sub) {
+                            anyhow::bail!("refreshed id token is for another subject");
+                        }
+                    }
+
+                    refreshed_token_record.redirect_uri = grant_record.redirect_uri.clone();
+
+                    // without rotation the refresh token stays valid
+                    if refreshed_token_record.refresh_token.is_none() {
+                        refreshed_token_record.refresh_token = grant_record.refresh_token.clone();
+                    }
+
+                    // rotated refresh tokens keep the lifetime of the login unless the provider announces another
+                    if refreshed_token_record.refresh_expires_at.is_none() {
+                        refreshed_token_record.refresh_expires_at = grant_record.refresh_expires_at;
+                    }
+
+                    self.limit_session(&mut refreshed_token_record);
+
+                    match resource {
+                        // keep the id token validated at login, refresh responses may not include one
+                        None if refreshed_token_record.id_token.is_none() => {
+                            refreshed_token_record.id_token = grant_record.id_token;
+                        }
+                        None => {}
+                        // the rotated refresh token replaces the one of the login grant, the old one is spent
+                        Some(_) => token::TokenRecord {
+                            refresh_token: refreshed_token_record.refresh_token.take(),
+                            refresh_expires_at: refreshed_token_record.refresh_expires_at,
+                            ..grant_record
+                        }
+                        .persist(&self.persistence, None)?,
+                    }
+
+                    let token_record = refreshed_token_record;
+                    tracing::debug!("token_record {token_record:?}");
+                    token_record.persist(&self.persistence, resource)?;
+                    return Ok(Some(token_record));
                 }
                 Err(err) => {
                     tracing::warn!("failed to refresh token: {}", err);
-                    token_response.unpersist(self.persistence)?;
-                    self.login_with_redirect().await?;
+                    // the rejected grant was shared by every resource
+                    token::TokenRecord::unpersist_all(&self.persistence)?;
+                    self.login_required().await?;
                     return Ok(None);
                 }
             }
         }
 
-        self.login_with_redirect().await?;
+        self.login_required().await?;
 
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use reqwest::{Method, StatusCode};
+    use std::cell::Cell;
+
+    use super::*;
+
+    const ORIGIN: &str = "http://localhost:8080";
+    const ISSUER: &str = "https://login.test";
+    const DISCOVERY_URL: &str = "https://login.test/.well-known/openid-configuration";
+    const AUTHORIZATION_ENDPOINT: &str = "https://login.test/authorize";
+    const TOKEN_ENDPOINT: &str = "https://login.test/token";
+    const JWKS_URI: &str = "https://login.test/jwks";
+
+    fn provider() -> http::RecordingClient {
+        http::RecordingClient::default().with_handler(|request| match (&request.method, request.url.as_str()) {
+            (&Method::GET, DISCOVERY_URL) => http::Response::from_json(
+                StatusCode::OK,
+                &serde_json::json!({
+                    "issuer": ISSUER,
+                    "authorization_endpoint": AUTHORIZATION_ENDPOINT,
+                    "token_endpoint": TOKEN_ENDPOINT,
+                }),
+            ),
+            (&Method::POST, TOKEN_ENDPOINT) => http::Response::from_json(
+                StatusCode::OK,
+                &serde_json::json!({
+                    "access_token": "access",
+                    "token_type": "Bearer",
+                    "expires_in": 3600,
+                    "refresh_token": "refresh",
+                    "scope": "User.Read",
+                }),
+            ),
+            _ => Ok(http::Response::new(StatusCode::NOT_FOUND, Vec::new())),
+        })
+    }
+
+    fn flow(platform: &platform::FakePlatform, http_client: &http::RecordingClient) -> AuthorizationCodeFlowWithPKCE {
+        AuthorizationCodeFlowWithPKCE::default()
+            .with_oidc_url(DISCOVERY_URL)
+            .with_client_id("client")
+            .with_scope("User.Read")
+            .with_platform(platform.clone())
+            .with_http_client(http_client.clone())
+            .with_memory_storage()
+    }
+
+    fn query(url: &Url) -> HashMap<String, String> {
+        url.query_pairs().into_owned().collect()
+    }
+
+    #[test]
+    fn navigates_to_the_authorize_endpoint() {
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let client = flow(&platform, &provider());
+
+        block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+
+        let navigations = platform.navigations();
+        assert_eq!(navigations.len(), 1);
+
+        let authorize_url = Url::parse(&navigations[0]).unwrap();
+        assert!(authorize_url.as_str().starts_with(AUTHORIZATION_ENDPOINT));
+
+        let params = query(&authorize_url);
+        assert_eq!(params["client_id"], "client");
+        assert_eq!(params["redirect_uri"], ORIGIN);
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["state"], csrf::State::retrieve(&client.persistence).unwrap().as_str());
+        assert!(!client.is_authenticated.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn authorization_errors_end_the_login() {
+        let callback_url = Url::parse(&format!("{ORIGIN}/?error=login_required&error_description=signed+out")).unwrap();
+        let platform = platform::FakePlatform::new(ORIGIN).with_current_url(callback_url);
+        let client = flow(&platform, &provider());
+
+        let error = block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap_err();
+
+        assert!(error::ends_session(&error));
+        assert_eq!(error.to_string(), "login_required: signed out");
+        assert!(platform.navigations().is_empty());
+        assert!(platform.is_history_cleaned());
+    }
+
+    #[test]
+    fn exchanges_the_code_with_the_authorized_redirect_uri() {
+        let platform = platform::FakePlatform::new(ORIGIN).with_responder(|authorize_url| {
+            let params = query(authorize_url);
+
+            let mut callback_url = Url::parse(&params["redirect_uri"]).ok()?;
+            callback_url
+                .query_pairs_mut()
+                .append_pair("code", "code")
+                .append_pair("state", &params["state"]);
+
+            Some(callback_url)
+        });
+        let http_client = provider();
+        let client = flow(&platform, &http_client);
+
+        block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+
+        let authorize_params = query(&Url::parse(&platform.navigations()[0]).unwrap());
+
+        let requests = http_client.requests();
+        let token_request = requests.iter().find(|request| request.url == TOKEN_ENDPOINT).unwrap();
+        let token_params: HashMap<String, String> = token_request.form().into_iter().collect();
+
+        assert_eq!(token_request.method, Method::POST);
+        assert_eq!(token_params["grant_type"], "authorization_code");
+        assert_eq!(token_params["code"], "code");
+        assert_eq!(token_params["redirect_uri"], authorize_params["redirect_uri"]);
+        assert_eq!(
+            pkce::CodeChallenge::from(&pkce::CodeVerifier {
+                value: token_params["code_verifier"].clone(),
+            })
+            .as_str(),
+            authorize_params["code_challenge"]
+        );
+
+        let token_record = token::TokenRecord::retrieve_unchecked(&client.persistence, None)
+            .unwrap()
+            .unwrap();
+
+        assert!(client.is_authenticated.load(Ordering::Acquire));
+        assert_eq!(token_record.access_token, "access");
+        assert_eq!(token_record.redirect_uri.as_deref(), Some(ORIGIN));
+        assert!(platform.is_history_cleaned());
+    }
+
+    #[test]
+    fn retries_transient_discovery_failures() {
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let http_client = provider();
+        http_client.push_response(http::Response::new(StatusCode::SERVICE_UNAVAILABLE, Vec::new()));
+
+        let client = flow(&platform, &http_client).with_retry_policy(retry::RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            ..retry::RetryPolicy::default()
+        });
+
+        block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+
+        let requests = http_client.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.url == DISCOVERY_URL));
+        assert_eq!(platform.navigations().len(), 1);
+    }
+
+    #[test]
+    fn caches_exchanged_tokens_per_subject() {
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let http_client = provider();
+        let client = flow(&platform, &http_client);
+
+        let exchange =
+            |subject_token| block_on(client.exchange_token(subject_token, None, Some("api://resource"), None)).unwrap();
+        let token_requests = || {
+            http_client
+                .requests()
+                .iter()
+                .filter(|request| request.url == TOKEN_ENDPOINT)
+                .count()
+        };
+
+        exchange("subject");
+        exchange("subject");
+        assert_eq!(token_requests(), 1);
+
+        exchange("other-subject");
+        assert_eq!(token_requests(), 2);
+    }
+
+    #[test]
+    fn plain_errors_end_jwt_response_mode_logins() {
+        let callback_url = Url::parse(&format!("{ORIGIN}/?error=access_denied")).unwrap();
+        let platform = platform::FakePlatform::new(ORIGIN).with_current_url(callback_url);
+        let client = flow(&platform, &provider()).with_jwt_response_mode();
+
+        let error = block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap_err();
+
+        assert_eq!(error.to_string(), "access_denied");
+        assert!(platform.navigations().is_empty());
+        assert!(platform.is_history_cleaned());
+    }
+
+    #[test]
+    fn invalid_jwt_responses_clear_the_transaction() {
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let http_client = provider().with_handler(|request| match request.url.as_str() {
+            DISCOVERY_URL => http::Response::from_json(
+                StatusCode::OK,
+                &serde_json::json!({
+                    "issuer": ISSUER,
+                    "authorization_endpoint": AUTHORIZATION_ENDPOINT,
+                    "token_endpoint": TOKEN_ENDPOINT,
+                    "jwks_uri": JWKS_URI,
+                }),
+            ),
+            JWKS_URI => http::Response::from_json(StatusCode::OK, &serde_json::json!({ "keys": [] })),
+            _ => Ok(http::Response::new(StatusCode::NOT_FOUND, Vec::new())),
+        });
+        let client = flow(&platform, &http_client).with_jwt_response_mode();
+
+        block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+        assert!(csrf::State::retrieve(&client.persistence).is_ok());
+
+        let callback_url = Url::parse(&format!("{ORIGIN}/?response=not-a-jwt")).unwrap();
+        let platform = platform.with_current_url(callback_url);
+
+        block_on(client.login_with_redirect(&params::AuthorizationOptions::default())).unwrap_err();
+
+        assert!(platform.is_history_cleaned());
+        assert!(csrf::State::retrieve(&client.persistence).is_err());
+        assert!(transaction::Transaction::retrieve(&client.persistence).is_err());
+    }
+
+    #[test]
+    fn follows_the_connectivity_of_the_platform() {
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let client = flow(&platform, &provider());
+
+        let is_online = Rc::new(Cell::new(true));
+        let watcher = is_online.clone();
+        client.watch_connectivity(Rc::new(move |online| watcher.set(online))).unwrap();
+
+        platform.set_online(false);
+
+        assert!(!client.is_online());
+        assert!(!is_online.get());
+    }
+}
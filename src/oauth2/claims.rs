@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::storage;
+use super::token;
+
+const USERINFO_KEY: &str = "oauth_userinfo";
+
+// protocol claims that only make sense while validating the id token
+const PROTOCOL_CLAIMS: &[&str] = &["iss", "aud", "exp", "iat", "nbf", "nonce", "at_hash", "c_hash"];
+
+// https://openid.net/specs/openid-connect-core-1_0.html#StandardClaims
+// https://learn.microsoft.com/en-us/entra/identity-platform/id-token-claims-reference
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct UserProfile {
+    pub sub: String,
+    pub name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub nickname: Option<String>,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub picture: Option<String>,
+    pub locale: Option<String>,
+    pub zoneinfo: Option<String>,
+    pub updated_at: Option<i64>,
+    pub auth_time: Option<i64>,
+
+    // azure
+    pub oid: Option<String>,
+    pub tid: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub wids: Vec<String>,
+
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl FromStr for UserProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut profile = token::decode_jwt_payload::<Self>(s)?;
+        profile.extra.retain(|claim, _| !PROTOCOL_CLAIMS.contains(&claim.as_str()));
+        Ok(profile)
+    }
+}
+
+impl UserProfile {
+    /// Overrides the claims with the ones returned by the userinfo endpoint, which are fresher than the id token ones.
+    pub fn merge(&mut self, userinfo: Self) {
+        let Self {
+            sub: _,
+            name,
+            given_name,
+            family_name,
+            nickname,
+            preferred_username,
+            email,
+            email_verified,
+            picture,
+            locale,
+            zoneinfo,
+            updated_at,
+            auth_time,
+            oid,
+            tid,
+            roles,
+            groups,
+            wids,
+            extra,
+        } = userinfo;
+
+        self.name = name.or(self.name.take());
+        self.given_name = given_name.or(self.given_name.take());
+        self.family_name = family_name.or(self.family_name.take());
+        self.nickname = nickname.or(self.nickname.take());
+        self.preferred_username = preferred_username.or(self.preferred_username.take());
+        self.email = email.or(self.email.take());
+        self.email_verified = email_verified.or(self.email_verified.take());
+        self.picture = picture.or(self.picture.take());
+        self.locale = locale.or(self.locale.take());
+        self.zoneinfo = zoneinfo.or(self.zoneinfo.take());
+        self.updated_at = updated_at.or(self.updated_at.take());
+        self.auth_time = auth_time.or(self.auth_time.take());
+        self.oid = oid.or(self.oid.take());
+        self.tid = tid.or(self.tid.take());
+
+        if !roles.is_empty() {
+            self.roles = roles;
+        }
+
+        if !groups.is_empty() {
+            self.groups = groups;
+        }
+
+        if !wids.is_empty() {
+            self.wids = wids;
+        }
+
+        self.extra.extend(extra);
+    }
+
+    pub fn retrieve_userinfo(storage_type: &storage::StorageType) -> anyhow::Result<Option<Self>> {
+        let storage = storage::get(storage_type)?;
+
+        if let Ok(Some(userinfo)) = storage.get_item(USERINFO_KEY) {
+            return Ok(Some(serde_json::from_str(&userinfo)?));
+        }
+
+        Ok(None)
+    }
+
+    pub fn persist_userinfo(&self, storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(_) = storage.set_item(USERINFO_KEY, &serde_json::to_string(&self)?) else {
+            anyhow::bail!("failed to save userinfo");
+        };
+
+        Ok(())
+    }
+
+    pub fn unpersist_userinfo(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(_) = storage.remove_item(USERINFO_KEY) else {
+            anyhow::bail!("failed to remove userinfo");
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use super::http;
+use super::token;
+
+/// Tolerance when comparing timestamps issued by the provider with the calibrated clock.
+pub const DEFAULT_SKEW: Duration = Duration::from_secs(5);
+
+/// Provider clock of a client as far as it has been calibrated, its clones share the calibration.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    // seconds the provider clock is ahead of the local one
+    offset: Rc<Cell<i64>>,
+    /// Tolerance when checking expiry and the auth_time, exp and nbf claims.
+    pub skew: Duration,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            offset: Rc::default(),
+            skew: DEFAULT_SKEW,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct IssuedAt {
+    iat: i64,
+}
+
+impl Clock {
+    /// Current unix time on the provider clock.
+    pub fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.offset.get()
+    }
+
+    pub fn skew_secs(&self) -> i64 {
+        self.skew.as_secs() as i64
+    }
+
+    pub fn calibrate(&self, server_time: i64) {
+        self.offset.set(server_time - chrono::Utc::now().timestamp());
+    }
+
+    /// Calibrates against the `Date` header, which browsers only expose when the provider allows it through cors,
+    /// or else the `iat` of the freshly issued tokens. Azure backdates `iat` by about five minutes, so it is only a fallback.
+    pub fn calibrate_from_token_response(&self, response: &http::Response, token_response: &token::TokenResponse) {
+        // https://www.rfc-editor.org/rfc/rfc9110#section-6.6.1
+        if let Some(date) = response
+            .header(reqwest::header::DATE.as_str())
+            .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok())
+        {
+            self.calibrate(date.timestamp());
+            return;
+        }
+
+        let issued_at = [token_response.id_token.as_deref(), Some(token_response.access_token.as_str())]
+            .into_iter()
+            .flatten()
+            .find_map(|jwt| token::decode_jwt_payload::<IssuedAt>(jwt).ok());
+
+        if let Some(IssuedAt { iat }) = issued_at {
+            self.calibrate(iat);
+        }
+    }
+}
@@ -11,6 +11,12 @@ pub struct State {
     pub value: String,
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl State {
     pub fn new() -> Self {
         let mut buffer = [0u8; 12];
@@ -25,21 +31,8 @@ impl State {
         self.value.as_str()
     }
 
-    pub fn retrieve(storage_type: storage::StorageType) -> anyhow::Result<Self> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn retrieve(storage_type: &storage::StorageType) -> anyhow::Result<Self> {
+        let storage = storage::get(storage_type)?;
 
         if let Ok(Some(state)) = storage.get_item(CSRF_STATE_KEY) {
             return Ok(Self { value: state });
@@ -48,7 +41,7 @@ impl State {
         anyhow::bail!("no state available");
     }
 
-    pub fn exists_and_matches_raw(storage_type: storage::StorageType, n: &str) -> bool {
+    pub fn exists_and_matches_raw(storage_type: &storage::StorageType, n: &str) -> bool {
         if let Ok(state) = Self::retrieve(storage_type) {
             return state.as_str() == n;
         }
@@ -56,21 +49,8 @@ impl State {
         false
     }
 
-    pub fn persist(&self, storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn persist(&self, storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.set_item(CSRF_STATE_KEY, &self.value) else {
             anyhow::bail!("failed to save state");
@@ -79,21 +59,8 @@ impl State {
         Ok(())
     }
 
-    pub fn unpersist(storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn unpersist(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.remove_item(CSRF_STATE_KEY) else {
             anyhow::bail!("failed to save state");
@@ -107,6 +74,12 @@ pub struct Nonce {
     pub value: String,
 }
 
+impl Default for Nonce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Nonce {
     pub fn new() -> Self {
         let mut buffer = [0u8; 12];
@@ -121,21 +94,8 @@ impl Nonce {
         self.value.as_str()
     }
 
-    pub fn retrieve(storage_type: storage::StorageType) -> anyhow::Result<Self> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn retrieve(storage_type: &storage::StorageType) -> anyhow::Result<Self> {
+        let storage = storage::get(storage_type)?;
 
         if let Ok(Some(state)) = storage.get_item(CSRF_NONCE_KEY) {
             return Ok(Self { value: state });
@@ -144,7 +104,7 @@ impl Nonce {
         anyhow::bail!("no nonce available");
     }
 
-    pub fn exists_and_matches_raw(storage_type: storage::StorageType, n: &str) -> bool {
+    pub fn exists_and_matches_raw(storage_type: &storage::StorageType, n: &str) -> bool {
         if let Ok(state) = Self::retrieve(storage_type) {
             return state.as_str() == n;
         }
@@ -152,21 +112,8 @@ impl Nonce {
         false
     }
 
-    pub fn persist(&self, storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn persist(&self, storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.set_item(CSRF_NONCE_KEY, &self.value) else {
             anyhow::bail!("failed to save state");
@@ -175,21 +122,8 @@ impl Nonce {
         Ok(())
     }
 
-    pub fn unpersist(storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn unpersist(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.remove_item(CSRF_NONCE_KEY) else {
             anyhow::bail!("failed to save state");
@@ -0,0 +1,123 @@
+use futures::channel::oneshot;
+use futures::future::LocalBoxFuture;
+use std::sync::Mutex;
+use url::Url;
+
+use super::platform::Platform;
+
+// login waiting for the redirect while the app keeps running
+static PENDING: Mutex<Option<oneshot::Sender<Url>>> = Mutex::new(None);
+
+// redirect that launched the app after it was closed during the login
+static DELIVERED: Mutex<Option<Url>> = Mutex::new(None);
+
+/// Hands a url opened through the app's custom scheme over to the login, called by the mobile shell.
+pub fn deliver(url: &str) -> anyhow::Result<()> {
+    let url = Url::parse(url)?;
+
+    let Ok(mut pending) = PENDING.lock() else {
+        anyhow::bail!("deep link receiver not available");
+    };
+
+    let url = match pending.take() {
+        Some(sender) => match sender.send(url) {
+            Ok(()) => return Ok(()),
+            Err(url) => url,
+        },
+        None => url,
+    };
+
+    let Ok(mut delivered) = DELIVERED.lock() else {
+        anyhow::bail!("deep link receiver not available");
+    };
+
+    *delivered = Some(url);
+
+    Ok(())
+}
+
+/// Native app redirect for mobile builds through a claimed private-use uri scheme, e.g. `com.example.app:/oauth2redirect`.
+/// The shell opens the authorize url in an in-app browser tab and forwards the redirect with `deliver`.
+// https://datatracker.ietf.org/doc/html/rfc8252#section-7.1
+#[derive(Debug, Clone, Copy)]
+pub struct DeepLinkPlatform {
+    redirect_uri: &'static str,
+    open_url: fn(&str) -> anyhow::Result<()>,
+}
+
+impl DeepLinkPlatform {
+    pub fn new(redirect_uri: &'static str, open_url: fn(&str) -> anyhow::Result<()>) -> Self {
+        Self { redirect_uri, open_url }
+    }
+
+    fn is_redirect(&self, url: &Url) -> bool {
+        let mut target = url.clone();
+        target.set_query(None);
+        target.set_fragment(None);
+
+        target.as_str() == self.redirect_uri
+    }
+}
+
+impl Platform for DeepLinkPlatform {
+    fn origin(&self) -> anyhow::Result<String> {
+        Ok(self.redirect_uri.to_owned())
+    }
+
+    fn current_url(&self) -> anyhow::Result<Option<Url>> {
+        let Ok(mut delivered) = DELIVERED.lock() else {
+            anyhow::bail!("deep link receiver not available");
+        };
+
+        Ok(delivered.take().filter(|url| self.is_redirect(url)))
+    }
+
+    /// Opens the url in the in-app browser tab, app routes can't be reached from there.
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        let Ok(url) = Url::parse(url) else {
+            anyhow::bail!("navigation to {url} not available outside the app");
+        };
+
+        (self.open_url)(url.as_str())
+    }
+
+    fn clean_history(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn wait_for_callback(&self) -> LocalBoxFuture<'_, anyhow::Result<Option<Url>>> {
+        Box::pin(async move {
+            let (sender, receiver) = oneshot::channel();
+
+            // a newer login replaces the waiting one, which then fails as canceled
+            match PENDING.lock() {
+                Ok(mut pending) => *pending = Some(sender),
+                Err(_) => anyhow::bail!("deep link receiver not available"),
+            }
+
+            let url = receiver.await?;
+
+            if !self.is_redirect(&url) {
+                anyhow::bail!("unexpected redirect {url}");
+            }
+
+            Ok(Some(url))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_routes_are_not_opened_in_the_browser() {
+        let platform = DeepLinkPlatform::new("com.example.app:/oauth2redirect", |url| anyhow::bail!("opened {url}"));
+
+        let error = platform.navigate("/forbidden").unwrap_err();
+        assert_eq!(error.to_string(), "navigation to /forbidden not available outside the app");
+
+        let error = platform.navigate("https://login.test/authorize").unwrap_err();
+        assert_eq!(error.to_string(), "opened https://login.test/authorize");
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.5
+const DEFAULT_INTERVAL: u64 = 5;
+const SLOW_DOWN_INCREMENT: u64 = 5;
+
+pub const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.2
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: i64,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    pub message: Option<String>,
+}
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.5
+#[derive(Debug, Deserialize)]
+pub struct DeviceTokenError {
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+pub enum PollOutcome {
+    Pending,
+    SlowDown,
+}
+
+impl DeviceTokenError {
+    pub fn into_outcome(self) -> anyhow::Result<PollOutcome> {
+        match self.error.as_str() {
+            "authorization_pending" => Ok(PollOutcome::Pending),
+            "slow_down" => Ok(PollOutcome::SlowDown),
+            _ => anyhow::bail!(
+                "device authorization failed {}: {}",
+                self.error,
+                self.error_description.unwrap_or_default()
+            ),
+        }
+    }
+}
+
+impl PollOutcome {
+    pub fn next_interval(&self, interval: u64) -> u64 {
+        match self {
+            Self::Pending => interval,
+            Self::SlowDown => interval + SLOW_DOWN_INCREMENT,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::anyhow;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use url::Url;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::clock;
+use super::http;
+
+pub const DPOP_HEADER: &str = "DPoP";
+pub const DPOP_NONCE_HEADER: &str = "DPoP-Nonce";
+pub const DPOP_TOKEN_TYPE: &str = "DPoP";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PublicJwk {
+    crv: String,
+    kty: String,
+    x: String,
+    y: String,
+}
+
+#[derive(Debug, Clone)]
+struct Key {
+    key_pair: web_sys::CryptoKeyPair,
+    jwk: PublicJwk,
+}
+
+#[derive(Serialize)]
+struct ProofHeader<'a> {
+    typ: &'a str,
+    alg: &'a str,
+    jwk: &'a PublicJwk,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9449#section-4.2
+#[derive(Serialize)]
+struct ProofClaims<'a> {
+    jti: String,
+    htm: &'a str,
+    htu: &'a str,
+    iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ath: Option<String>,
+}
+
+fn origin(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+/// Holds the P-256 key of the current session, generated as non-extractable so the private key never leaves WebCrypto.
+#[derive(Debug, Clone)]
+pub struct DPoP {
+    key: Rc<RefCell<Option<Key>>>,
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-8, each server provides its own nonce
+    nonces: Rc<RefCell<HashMap<String, String>>>,
+    clock: clock::Clock,
+}
+
+impl DPoP {
+    pub fn new(clock: clock::Clock) -> Self {
+        Self {
+            key: Rc::default(),
+            nonces: Rc::default(),
+            clock,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn subtle() -> anyhow::Result<web_sys::SubtleCrypto> {
+        anyhow::bail!("webcrypto not available outside the browser, dpop needs a web build");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn subtle() -> anyhow::Result<web_sys::SubtleCrypto> {
+        let Some(window) = web_sys::window() else {
+            anyhow::bail!("window not available");
+        };
+
+        let crypto = window.crypto().map_err(|err| anyhow!("crypto not available: {err:?}"))?;
+
+        Ok(crypto.subtle())
+    }
+
+    async fn key(&self) -> anyhow::Result<Key> {
+        if let Some(key) = self.key.borrow().clone() {
+            return Ok(key);
+        }
+
+        let subtle = Self::subtle()?;
+
+        let algorithm = web_sys::EcKeyGenParams::new("ECDSA", "P-256");
+        let usages = js_sys::Array::of2(&JsValue::from_str("sign"), &JsValue::from_str("verify"));
+
+        let key_pair = subtle
+            .generate_key_with_object(&algorithm, false, &usages)
+            .map_err(|err| anyhow!("failed to generate dpop key: {err:?}"))?;
+
+        let key_pair: web_sys::CryptoKeyPair = JsFuture::from(key_pair)
+            .await
+            .map_err(|err| anyhow!("failed to generate dpop key: {err:?}"))?
+            .unchecked_into();
+
+        let jwk = subtle
+            .export_key("jwk", &key_pair.get_public_key())
+            .map_err(|err| anyhow!("failed to export dpop public key: {err:?}"))?;
+
+        let jwk = JsFuture::from(jwk)
+            .await
+            .map_err(|err| anyhow!("failed to export dpop public key: {err:?}"))?;
+
+        let Some(jwk) = js_sys::JSON::stringify(&jwk).ok().and_then(|jwk| jwk.as_string()) else {
+            anyhow::bail!("failed to serialize dpop public key");
+        };
+
+        let key = Key {
+            key_pair,
+            jwk: serde_json::from_str(&jwk)?,
+        };
+
+        *self.key.borrow_mut() = Some(key.clone());
+
+        Ok(key)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7638#section-3
+    pub fn thumbprint(&self) -> Option<String> {
+        let key = self.key.borrow();
+        let jwk = &key.as_ref()?.jwk;
+
+        let canonical = format!(
+            r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#,
+            jwk.crv, jwk.kty, jwk.x, jwk.y
+        );
+
+        Some(URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes())))
+    }
+
+    pub async fn proof(&self, method: &str, url: &str, access_token: Option<&str>) -> anyhow::Result<String> {
+        let key = self.key().await?;
+
+        let mut htu = Url::parse(url)?;
+        htu.set_query(None);
+        htu.set_fragment(None);
+
+        let mut jti = [0u8; 16];
+        rand::rng().fill_bytes(&mut jti);
+
+        let header = ProofHeader {
+            typ: "dpop+jwt",
+            alg: "ES256",
+            jwk: &key.jwk,
+        };
+
+        let claims = ProofClaims {
+            jti: URL_SAFE_NO_PAD.encode(jti),
+            htm: method,
+            htu: htu.as_str(),
+            iat: self.clock.now(),
+            nonce: self.nonces.borrow().get(&origin(&htu)).cloned(),
+            ath: access_token.map(|access_token| URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes()))),
+        };
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+
+        let algorithm = web_sys::EcdsaParams::new("ECDSA", &JsValue::from_str("SHA-256"));
+
+        let signature = Self::subtle()?
+            .sign_with_object_and_u8_array(&algorithm, &key.key_pair.get_private_key(), signing_input.as_bytes())
+            .map_err(|err| anyhow!("failed to sign dpop proof: {err:?}"))?;
+
+        // webcrypto already returns the raw r || s signature expected by jws
+        let signature = JsFuture::from(signature)
+            .await
+            .map_err(|err| anyhow!("failed to sign dpop proof: {err:?}"))?;
+
+        let signature = js_sys::Uint8Array::new(&signature).to_vec();
+
+        Ok(format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    /// Stores the nonce provided by the server at the given url, returns whether it changed.
+    fn update_nonce(&self, url: &str, response: &http::Response) -> anyhow::Result<bool> {
+        let Some(nonce) = response.header(DPOP_NONCE_HEADER) else {
+            return Ok(false);
+        };
+
+        let previous = self.nonces.borrow_mut().insert(origin(&Url::parse(url)?), nonce.to_owned());
+
+        Ok(previous.as_deref() != Some(nonce))
+    }
+
+    /// Sends the request with a fresh proof, retrying once when the server challenges with a new nonce.
+    pub async fn send(
+        &self,
+        http_client: &dyn http::HttpClient,
+        request: http::Request,
+        access_token: Option<&str>,
+    ) -> anyhow::Result<http::Response> {
+        let method = request.method.as_str();
+
+        let proof = self.proof(method, &request.url, access_token).await?;
+        let response = http_client.send(request.clone().with_header(DPOP_HEADER, &proof)).await?;
+
+        // https://datatracker.ietf.org/doc/html/rfc9449#section-8
+        if self.update_nonce(&request.url, &response)?
+            && matches!(response.status, StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED)
+        {
+            let proof = self.proof(method, &request.url, access_token).await?;
+            return http_client.send(request.clone().with_header(DPOP_HEADER, &proof)).await;
+        }
+
+        Ok(response)
+    }
+}
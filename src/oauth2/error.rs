@@ -0,0 +1,37 @@
+use serde::Deserialize;
+use std::fmt;
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+// https://openid.net/specs/openid-connect-core-1_0.html#AuthError
+/// Error answered by the provider, from the token endpoint or in the authorization response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Whether the provider rejected the session, only an interactive login continues it.
+    pub fn ends_session(&self) -> bool {
+        matches!(
+            self.error.as_str(),
+            "invalid_grant" | "login_required" | "interaction_required" | "consent_required" | "account_selection_required"
+        )
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error_description {
+            Some(error_description) => write!(f, "{}: {error_description}", self.error),
+            None => f.write_str(&self.error),
+        }
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
+/// Whether the error is an error response of the provider ending the session.
+pub fn ends_session(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<ErrorResponse>().is_some_and(ErrorResponse::ends_session)
+}
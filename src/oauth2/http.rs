@@ -0,0 +1,250 @@
+use futures::future::LocalBoxFuture;
+use reqwest::{Method, StatusCode, header::HeaderMap};
+use serde::{Serialize, de::DeserializeOwned};
+#[cfg(any(test, feature = "test-support"))]
+use std::cell::RefCell;
+#[cfg(any(test, feature = "test-support"))]
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use url::form_urlencoded;
+
+use super::error;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl Request {
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_owned(),
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn post_form<K: AsRef<str>, V: AsRef<str>>(url: &str, params: &[(K, V)]) -> Self {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params.iter().map(|(key, value)| (key.as_ref(), value.as_ref())))
+            .finish();
+
+        let mut request =
+            Self::new(Method::POST, url).with_header(reqwest::header::CONTENT_TYPE.as_str(), "application/x-www-form-urlencoded");
+        request.body = Some(body);
+        request
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Decoded form parameters of the body.
+    pub fn form(&self) -> Vec<(String, String)> {
+        form_urlencoded::parse(self.body.as_deref().unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: StatusCode, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn from_json(status: StatusCode, body: &impl Serialize) -> anyhow::Result<Self> {
+        Ok(Self::new(status, serde_json::to_vec(body)?).with_header(reqwest::header::CONTENT_TYPE.as_str(), "application/json"))
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (name.parse::<reqwest::header::HeaderName>(), value.parse()) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Failures worth retrying, the server is unavailable or rate limiting.
+    pub fn is_transient(&self) -> bool {
+        self.status.is_server_error() || self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Fails on error statuses, with the `ErrorResponse` when the body carries one.
+    pub fn error_for_status(self) -> anyhow::Result<Self> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            if let Ok(error_response) = self.json::<error::ErrorResponse>() {
+                return Err(error_response.into());
+            }
+
+            anyhow::bail!("http status {}: {}", self.status, String::from_utf8_lossy(&self.body));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Transport every discovery, token and resource call goes through.
+pub trait HttpClient: fmt::Debug {
+    fn send(&self, request: Request) -> LocalBoxFuture<'_, anyhow::Result<Response>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpClientRef(Rc<dyn HttpClient>);
+
+impl HttpClientRef {
+    pub fn new(http_client: impl HttpClient + 'static) -> Self {
+        Self(Rc::new(http_client))
+    }
+}
+
+impl Default for HttpClientRef {
+    fn default() -> Self {
+        Self::new(ReqwestClient::default())
+    }
+}
+
+impl Deref for HttpClientRef {
+    type Target = dyn HttpClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Wraps a reqwest client, build one with custom headers, timeouts or proxies and pass it to `with_http_client`.
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl From<reqwest::Client> for ReqwestClient {
+    fn from(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpClient for ReqwestClient {
+    fn send(&self, request: Request) -> LocalBoxFuture<'_, anyhow::Result<Response>> {
+        Box::pin(async move {
+            let mut builder = self.client.request(request.method, &request.url);
+
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+
+            let response = builder.send().await?;
+
+            Ok(Response {
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response.bytes().await?.to_vec(),
+            })
+        })
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+type Handler = Rc<dyn Fn(&Request) -> anyhow::Result<Response>>;
+
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Default)]
+struct Recording {
+    requests: Vec<Request>,
+    responses: VecDeque<Response>,
+    handler: Option<Handler>,
+}
+
+/// Records every request and answers with queued responses, falling back to a handler.
+/// Clones share the recording, so a handle can be kept after passing it to `with_http_client`.
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Clone, Default)]
+pub struct RecordingClient {
+    recording: Rc<RefCell<Recording>>,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl fmt::Debug for RecordingClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let recording = self.recording.borrow();
+
+        f.debug_struct("RecordingClient")
+            .field("requests", &recording.requests)
+            .field("responses", &recording.responses)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl RecordingClient {
+    pub fn with_handler(self, handler: impl Fn(&Request) -> anyhow::Result<Response> + 'static) -> Self {
+        self.recording.borrow_mut().handler = Some(Rc::new(handler));
+        self
+    }
+
+    pub fn push_response(&self, response: Response) {
+        self.recording.borrow_mut().responses.push_back(response);
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.recording.borrow().requests.clone()
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl HttpClient for RecordingClient {
+    fn send(&self, request: Request) -> LocalBoxFuture<'_, anyhow::Result<Response>> {
+        Box::pin(async move {
+            let (response, handler) = {
+                let mut recording = self.recording.borrow_mut();
+                recording.requests.push(request.clone());
+                (recording.responses.pop_front(), recording.handler.clone())
+            };
+
+            match (response, handler) {
+                (Some(response), _) => Ok(response),
+                (None, Some(handler)) => handler(&request),
+                (None, None) => anyhow::bail!("no response recorded for {} {}", request.method, request.url),
+            }
+        })
+    }
+}
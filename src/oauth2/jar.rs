@@ -0,0 +1,118 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use std::fmt;
+
+use super::clock;
+use super::params::Params;
+
+const REQUEST_OBJECT_TYPE: &str = "oauth-authz-req+jwt";
+
+// lifetime in seconds of a request object
+const REQUEST_OBJECT_LIFETIME: i64 = 60;
+
+// https://datatracker.ietf.org/doc/html/rfc9101
+#[derive(Clone)]
+pub enum RequestObject {
+    /// `alg=none` request objects for public clients, only where the provider accepts them.
+    Unsigned,
+    /// Request objects signed with a key held by the client, e.g. desktop and mobile builds.
+    Signed {
+        algorithm: Algorithm,
+        key: EncodingKey,
+        kid: Option<String>,
+    },
+}
+
+impl fmt::Debug for RequestObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsigned => f.write_str("Unsigned"),
+            Self::Signed { algorithm, kid, .. } => f
+                .debug_struct("Signed")
+                .field("algorithm", algorithm)
+                .field("kid", kid)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl RequestObject {
+    // https://datatracker.ietf.org/doc/html/rfc9101#section-4
+    fn claims(
+        params: &[(String, String)],
+        client_id: &str,
+        issuer: &str,
+        clock: &clock::Clock,
+    ) -> serde_json::Map<String, serde_json::Value> {
+        let mut claims = serde_json::Map::new();
+
+        for (key, value) in params {
+            let value = if *key == Params::MaxAge.to_string() {
+                value
+                    .parse::<i64>()
+                    .map(serde_json::Value::from)
+                    .unwrap_or(value.as_str().into())
+            } else if *key == Params::AuthorizationDetails.to_string() {
+                serde_json::from_str(value).unwrap_or(value.as_str().into())
+            } else {
+                serde_json::Value::from(value.as_str())
+            };
+
+            // repeated parameters, e.g. resource, become arrays
+            match claims.get_mut(key) {
+                Some(serde_json::Value::Array(values)) => values.push(value),
+                Some(previous) => *previous = serde_json::Value::Array(vec![previous.take(), value]),
+                None => {
+                    claims.insert(key.clone(), value);
+                }
+            }
+        }
+
+        let now = clock.now();
+
+        claims.insert("iss".to_owned(), client_id.into());
+        claims.insert("aud".to_owned(), issuer.into());
+        claims.insert("iat".to_owned(), now.into());
+        claims.insert("nbf".to_owned(), now.into());
+        claims.insert("exp".to_owned(), (now + REQUEST_OBJECT_LIFETIME).into());
+
+        claims
+    }
+
+    /// Unsigned request objects are only sent to providers advertising `none`, signed ones are assumed to be accepted.
+    pub fn is_supported(&self, signing_alg_values_supported: &[String]) -> bool {
+        match self {
+            Self::Unsigned => signing_alg_values_supported.iter().any(|alg| alg == "none"),
+            Self::Signed { .. } => true,
+        }
+    }
+
+    pub fn encode(
+        &self,
+        params: &[(String, String)],
+        client_id: &str,
+        issuer: &str,
+        clock: &clock::Clock,
+    ) -> anyhow::Result<String> {
+        let claims = Self::claims(params, client_id, issuer, clock);
+
+        match self {
+            Self::Unsigned => {
+                let header = serde_json::json!({ "alg": "none", "typ": REQUEST_OBJECT_TYPE });
+
+                Ok(format!(
+                    "{}.{}.",
+                    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+                    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+                ))
+            }
+            Self::Signed { algorithm, key, kid } => {
+                let mut header = Header::new(*algorithm);
+                header.typ = Some(REQUEST_OBJECT_TYPE.to_owned());
+                header.kid = kid.clone();
+
+                Ok(jsonwebtoken::encode(&header, &claims, key)?)
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+use super::clock;
+use super::error;
+use super::jwks;
+
+// https://openid.net/specs/oauth-v2-jarm.html#section-2.1
+#[derive(Debug, Deserialize)]
+pub struct AuthorizationResponse {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub id_token: Option<String>,
+    pub iss: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+impl AuthorizationResponse {
+    pub fn verify(
+        response: &str,
+        jwks: &jwks::JsonWebKeySet,
+        issuer: &str,
+        client_id: &str,
+        clock: &clock::Clock,
+    ) -> anyhow::Result<Self> {
+        let authorization_response = jwks.verify::<Self>(response, issuer, client_id, clock)?;
+
+        if let Some(error) = authorization_response.error {
+            return Err(error::ErrorResponse {
+                error,
+                error_description: authorization_response.error_description,
+            }
+            .into());
+        }
+
+        Ok(authorization_response)
+    }
+}
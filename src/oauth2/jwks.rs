@@ -0,0 +1,76 @@
+use jsonwebtoken::{DecodingKey, Validation, jwk::JwkSet};
+use serde::{Deserialize, de::DeserializeOwned};
+
+use super::clock;
+use super::http;
+use super::retry;
+
+#[derive(Deserialize)]
+struct TimeClaims {
+    exp: i64,
+    nbf: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct JsonWebKeySet {
+    pub keys: JwkSet,
+}
+
+impl JsonWebKeySet {
+    // TODO implement cache until the keys rotate
+    pub async fn from_remote(
+        http_client: &dyn http::HttpClient,
+        retry_policy: &retry::RetryPolicy,
+        jwks_uri: &str,
+    ) -> anyhow::Result<Self> {
+        let keys = retry::send(retry_policy, || http_client.send(http::Request::get(jwks_uri)))
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()?;
+        Ok(Self { keys })
+    }
+
+    /// Verifies the signature, issuer, audience and expiration of a jwt signed by the provider.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        jwt: &str,
+        issuer: &str,
+        audience: &str,
+        clock: &clock::Clock,
+    ) -> anyhow::Result<T> {
+        let header = jsonwebtoken::decode_header(jwt)?;
+
+        let Some(kid) = header.kid else {
+            anyhow::bail!("jwt header kid not available");
+        };
+
+        let Some(jwk) = self.keys.find(&kid) else {
+            anyhow::bail!("signing key {kid} not available in jwks");
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        // checked below against the calibrated clock instead of the local one
+        validation.validate_exp = false;
+        validation.validate_nbf = false;
+
+        let token_data = jsonwebtoken::decode::<serde_json::Value>(jwt, &DecodingKey::from_jwk(jwk)?, &validation)?;
+
+        let TimeClaims { exp, nbf } = serde_json::from_value(token_data.claims.clone())?;
+        let now = clock.now();
+        let skew = clock.skew_secs();
+
+        if now - skew >= exp {
+            anyhow::bail!("jwt expired");
+        }
+
+        if nbf.is_some_and(|nbf| now + skew < nbf) {
+            anyhow::bail!("jwt not yet valid");
+        }
+
+        Ok(serde_json::from_value(token_data.claims)?)
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::anyhow;
+use dioxus::logger::tracing;
+use futures::future::LocalBoxFuture;
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use super::platform::Platform;
+
+// time the user has to complete the login in the system browser
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+// connections without a request line, e.g. speculative preconnects of the browser, are dropped after it
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CALLBACK_PAGE: &str = "<html><body><p>Sign in complete, you can close this window.</p></body></html>";
+
+/// Native app redirect for desktop builds, the system browser redirects back to a listener on an ephemeral loopback port.
+/// The response only carries query parameters, so it can't be combined with the hybrid flow.
+// https://datatracker.ietf.org/doc/html/rfc8252#section-7.3
+#[derive(Debug, Clone, Default)]
+pub struct LoopbackPlatform {
+    listener: Rc<RefCell<Option<TcpListener>>>,
+}
+
+impl LoopbackPlatform {
+    fn listener_origin(listener: &TcpListener) -> anyhow::Result<String> {
+        Ok(format!("http://{}:{}", Ipv4Addr::LOCALHOST, listener.local_addr()?.port()))
+    }
+
+    /// Reads the request line, answering and returning the url only when it carries an authorization response.
+    fn read_callback(mut stream: TcpStream, origin: &Url) -> anyhow::Result<Option<Url>> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let mut request_line = String::new();
+
+        match BufReader::new(&stream).read_line(&mut request_line) {
+            Ok(_) => {}
+            Err(err) if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // e.g. GET /?code=...&state=... HTTP/1.1
+        let Some(target) = request_line.split_whitespace().nth(1) else {
+            anyhow::bail!("malformed loopback request");
+        };
+
+        let url = origin.join(target)?;
+
+        let is_callback = url.query_pairs().any(|(key, _)| key == "code" || key == "error");
+
+        if !is_callback {
+            stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+            return Ok(None);
+        }
+
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{CALLBACK_PAGE}",
+            CALLBACK_PAGE.len()
+        )?;
+
+        Ok(Some(url))
+    }
+
+    fn accept_callback(listener: TcpListener, origin: Url) -> anyhow::Result<Url> {
+        listener.set_nonblocking(true)?;
+
+        let deadline = Instant::now() + CALLBACK_TIMEOUT;
+
+        while Instant::now() < deadline {
+            match listener.accept() {
+                Ok((stream, _)) => match Self::read_callback(stream, &origin) {
+                    Ok(Some(url)) => return Ok(url),
+                    Ok(None) => {}
+                    Err(err) => tracing::warn!("failed to read loopback request: {err:?}"),
+                },
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(ACCEPT_INTERVAL),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        anyhow::bail!("timed out waiting for the authorization response");
+    }
+}
+
+impl Platform for LoopbackPlatform {
+    // any port of the loopback interface is accepted outside of an authorization request
+    fn origin(&self) -> anyhow::Result<String> {
+        match self.listener.borrow().as_ref() {
+            Some(listener) => Self::listener_origin(listener),
+            None => Ok(format!("http://{}", Ipv4Addr::LOCALHOST)),
+        }
+    }
+
+    /// Binds the listener waiting for the response, reused until `wait_for_callback` takes it.
+    fn redirect_uri(&self) -> anyhow::Result<String> {
+        let mut listener = self.listener.borrow_mut();
+
+        if let Some(listener) = listener.as_ref() {
+            return Self::listener_origin(listener);
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc8252#section-8.3
+        let bound = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let redirect_uri = Self::listener_origin(&bound)?;
+        *listener = Some(bound);
+
+        Ok(redirect_uri)
+    }
+
+    fn current_url(&self) -> anyhow::Result<Option<Url>> {
+        Ok(None)
+    }
+
+    /// Opens the url in the system browser, app routes can't be reached from there.
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        let Ok(url) = Url::parse(url) else {
+            anyhow::bail!("navigation to {url} not available outside the browser");
+        };
+
+        #[cfg(target_os = "windows")]
+        let mut command = {
+            let mut command = std::process::Command::new("rundll32");
+            command.arg("url.dll,FileProtocolHandler");
+            command
+        };
+
+        #[cfg(target_os = "macos")]
+        let mut command = std::process::Command::new("open");
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let mut command = std::process::Command::new("xdg-open");
+
+        command
+            .arg(url.as_str())
+            .spawn()
+            .map_err(|err| anyhow!("failed to open the system browser: {err}"))?;
+
+        Ok(())
+    }
+
+    fn clean_history(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn wait_for_callback(&self) -> LocalBoxFuture<'_, anyhow::Result<Option<Url>>> {
+        Box::pin(async move {
+            // the next login binds a new ephemeral port
+            let Some(listener) = self.listener.borrow_mut().take() else {
+                anyhow::bail!("loopback listener not available");
+            };
+
+            let origin = Url::parse(&Self::listener_origin(&listener)?)?;
+
+            let (sender, receiver) = futures::channel::oneshot::channel();
+
+            std::thread::spawn(move || {
+                let _ = sender.send(Self::accept_callback(listener, origin));
+            });
+
+            Ok(Some(receiver.await??))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn idle_connections_do_not_block_the_callback() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let origin = Url::parse(&LoopbackPlatform::listener_origin(&listener).unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let browser = std::thread::spawn(move || {
+            let _preconnect = TcpStream::connect(address).unwrap();
+
+            let mut callback = TcpStream::connect(address).unwrap();
+            callback.write_all(b"GET /?code=code&state=state HTTP/1.1\r\n\r\n").unwrap();
+
+            let mut response = String::new();
+            callback.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        let url = LoopbackPlatform::accept_callback(listener, origin).unwrap();
+
+        assert_eq!(url.query(), Some("code=code&state=state"));
+        assert!(browser.join().unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+}
@@ -0,0 +1,682 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use futures::future::LocalBoxFuture;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rand::RngCore;
+use reqwest::{Method, StatusCode};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use url::Url;
+
+use super::azure;
+use super::claims;
+use super::http;
+use super::params::Params;
+use super::platform;
+
+pub const ISSUER: &str = "https://mock-provider.test";
+pub const DISCOVERY_URL: &str = "https://mock-provider.test/.well-known/openid-configuration";
+
+const AUTHORIZATION_PATH: &str = "/authorize";
+const TOKEN_PATH: &str = "/token";
+const JWKS_PATH: &str = "/jwks";
+const USERINFO_PATH: &str = "/userinfo";
+const REVOCATION_PATH: &str = "/revoke";
+const END_SESSION_PATH: &str = "/logout";
+
+// lifetime in seconds of the issued tokens
+const TOKEN_LIFETIME: i64 = 3600;
+
+const WRONG_NONCE: &str = "mock-wrong-nonce";
+
+/// Misbehaviours the provider can be told to show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Failure {
+    /// Every token request is rejected with `invalid_grant`.
+    InvalidGrant,
+    /// Id tokens carry a nonce other than the requested one.
+    WrongNonce,
+    /// Tokens are issued already expired.
+    ExpiredTokens,
+    /// The token endpoint answers 503.
+    Unavailable,
+    /// The token endpoint answers 429 with a `Retry-After` of one second.
+    RateLimited,
+}
+
+#[derive(Debug, Clone)]
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    scope: String,
+    nonce: Option<String>,
+    code_challenge: String,
+}
+
+#[derive(Debug)]
+struct SigningKey {
+    kid: String,
+    secret: Vec<u8>,
+}
+
+impl SigningKey {
+    fn generate() -> Self {
+        let mut secret = vec![0u8; 32];
+        rand::rng().fill_bytes(&mut secret);
+
+        Self {
+            kid: random_token(),
+            secret,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    user: claims::UserProfile,
+    key: SigningKey,
+    codes: HashMap<String, Grant>,
+    refresh_tokens: HashMap<String, Grant>,
+    access_tokens: HashMap<String, i64>,
+    failures: HashSet<Failure>,
+}
+
+fn random_token() -> String {
+    let mut buffer = [0u8; 24];
+    rand::rng().fill_bytes(&mut buffer);
+    URL_SAFE_NO_PAD.encode(buffer)
+}
+
+fn endpoint(path: &str) -> String {
+    format!("{ISSUER}{path}")
+}
+
+fn oauth_error(status: StatusCode, error: &str) -> anyhow::Result<http::Response> {
+    http::Response::from_json(status, &serde_json::json!({ "error": error }))
+}
+
+/// In-process OpenID provider answering the flow's http requests and browser navigations, auto-approving every
+/// authorization for the configured user. Tokens are signed with random HS256 keys published in the jwks.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    state: Rc<RefCell<State>>,
+}
+
+impl MockProvider {
+    pub fn new(user: claims::UserProfile) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(State {
+                user,
+                key: SigningKey::generate(),
+                codes: HashMap::new(),
+                refresh_tokens: HashMap::new(),
+                access_tokens: HashMap::new(),
+                failures: HashSet::new(),
+            })),
+        }
+    }
+
+    /// Flow wired to the provider, with a fake browser on the given origin and in-memory storage.
+    pub fn flow(&self, client_id: &'static str, scope: &'static str, origin: &str) -> azure::AuthorizationCodeFlowWithPKCE {
+        azure::AuthorizationCodeFlowWithPKCE::default()
+            .with_oidc_url(DISCOVERY_URL)
+            .with_client_id(client_id)
+            .with_scope(scope)
+            .with_http_client(self.clone())
+            .with_platform(self.platform(origin))
+            .with_memory_storage()
+    }
+
+    /// Fake browser that follows the authorize and end session redirects of the provider.
+    pub fn platform(&self, origin: &str) -> platform::FakePlatform {
+        let provider = self.clone();
+        platform::FakePlatform::new(origin).with_responder(move |url| provider.navigate(url))
+    }
+
+    pub fn set_user(&self, user: claims::UserProfile) {
+        self.state.borrow_mut().user = user;
+    }
+
+    pub fn inject(&self, failure: Failure) {
+        self.state.borrow_mut().failures.insert(failure);
+    }
+
+    pub fn clear_failures(&self) {
+        self.state.borrow_mut().failures.clear();
+    }
+
+    /// Replaces the signing key, tokens signed before no longer verify against the jwks.
+    pub fn rotate_keys(&self) {
+        self.state.borrow_mut().key = SigningKey::generate();
+    }
+
+    fn has_failure(&self, failure: Failure) -> bool {
+        self.state.borrow().failures.contains(&failure)
+    }
+
+    /// Answers a browser navigation with the redirect the provider would send back.
+    pub fn navigate(&self, url: &Url) -> Option<Url> {
+        if !url.as_str().starts_with(ISSUER) {
+            return None;
+        }
+
+        match url.path() {
+            AUTHORIZATION_PATH => self.authorize(url),
+            END_SESSION_PATH => self.end_session(url),
+            _ => None,
+        }
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#AuthResponse
+    fn authorize(&self, url: &Url) -> Option<Url> {
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        let param = |key: Params| params.get(&key.to_string()).cloned();
+
+        let mut redirect_uri = Url::parse(&param(Params::RedirectUri)?).ok()?;
+
+        let response_type = param(Params::ResponseType).unwrap_or_default();
+        let is_hybrid = response_type
+            .split_whitespace()
+            .any(|response_type| response_type == "id_token");
+
+        let mut response = match self.authorization_grant(&params, is_hybrid) {
+            Ok(response) => response,
+            Err(error) => vec![("error".to_owned(), error.to_owned())],
+        };
+
+        if let Some(state) = param(Params::State) {
+            response.push((Params::State.to_string(), state));
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc9207#section-2
+        response.push(("iss".to_owned(), ISSUER.to_owned()));
+
+        let encoded = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(response)
+            .finish();
+
+        match param(Params::ResponseMode).as_deref() {
+            Some("fragment") => redirect_uri.set_fragment(Some(&encoded)),
+            None if is_hybrid => redirect_uri.set_fragment(Some(&encoded)),
+            _ => redirect_uri.set_query(Some(&encoded)),
+        }
+
+        Some(redirect_uri)
+    }
+
+    fn authorization_grant(
+        &self,
+        params: &HashMap<String, String>,
+        is_hybrid: bool,
+    ) -> Result<Vec<(String, String)>, &'static str> {
+        let param = |key: Params| params.get(&key.to_string()).cloned();
+
+        if param(Params::Request).is_some() || param(Params::RequestUri).is_some() {
+            return Err("request_not_supported");
+        }
+
+        if param(Params::ResponseMode).is_some_and(|response_mode| response_mode.ends_with(".jwt")) {
+            return Err("unsupported_response_mode");
+        }
+
+        if !matches!(param(Params::ResponseType).as_deref(), Some("code" | "code id_token")) {
+            return Err("unsupported_response_type");
+        }
+
+        // https://datatracker.ietf.org/doc/html/rfc7636#section-4.4.1
+        let (Some(code_challenge), Some("S256")) = (param(Params::CodeChallenge), param(Params::CodeChallengeMethod).as_deref())
+        else {
+            return Err("invalid_request");
+        };
+
+        let (Some(client_id), Some(redirect_uri)) = (param(Params::ClientId), param(Params::RedirectUri)) else {
+            return Err("invalid_request");
+        };
+
+        let grant = Grant {
+            client_id,
+            redirect_uri,
+            scope: param(Params::Scope).unwrap_or_default(),
+            nonce: param(Params::Nonce),
+            code_challenge,
+        };
+
+        let code = random_token();
+        let mut response = vec![(Params::Code.to_string(), code.clone())];
+
+        if is_hybrid {
+            let id_token = self.id_token(&grant).map_err(|_| "server_error")?;
+            response.push(("id_token".to_owned(), id_token));
+        }
+
+        self.state.borrow_mut().codes.insert(code, grant);
+
+        Ok(response)
+    }
+
+    // https://openid.net/specs/openid-connect-rpinitiated-1_0.html#RedirectionAfterLogout
+    fn end_session(&self, url: &Url) -> Option<Url> {
+        self.state.borrow_mut().refresh_tokens.clear();
+
+        let post_logout_redirect_uri = url
+            .query_pairs()
+            .find(|(key, _)| *key == Params::PostLogoutRedirectUri.to_string())?
+            .1;
+
+        Url::parse(&post_logout_redirect_uri).ok()
+    }
+
+    fn sign(&self, claims: &serde_json::Value) -> anyhow::Result<String> {
+        let state = self.state.borrow();
+
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(state.key.kid.clone());
+
+        Ok(jsonwebtoken::encode(
+            &header,
+            claims,
+            &EncodingKey::from_secret(&state.key.secret),
+        )?)
+    }
+
+    fn expires_in(&self) -> i64 {
+        if self.has_failure(Failure::ExpiredTokens) {
+            0
+        } else {
+            TOKEN_LIFETIME
+        }
+    }
+
+    fn user_claims(&self) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+        let serde_json::Value::Object(mut claims) = serde_json::to_value(&self.state.borrow().user)? else {
+            anyhow::bail!("user claims are not an object");
+        };
+
+        claims.retain(|_, value| !value.is_null());
+
+        Ok(claims)
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#IDToken
+    fn id_token(&self, grant: &Grant) -> anyhow::Result<String> {
+        let now = chrono::Utc::now().timestamp();
+
+        let mut claims = self.user_claims()?;
+        claims.insert("iss".to_owned(), ISSUER.into());
+        claims.insert("aud".to_owned(), grant.client_id.as_str().into());
+        claims.insert("iat".to_owned(), now.into());
+        claims.insert("exp".to_owned(), (now + self.expires_in()).into());
+        claims.entry("auth_time").or_insert(now.into());
+
+        let nonce = if self.has_failure(Failure::WrongNonce) {
+            Some(WRONG_NONCE.to_owned())
+        } else {
+            grant.nonce.clone()
+        };
+
+        if let Some(nonce) = nonce {
+            claims.insert("nonce".to_owned(), nonce.into());
+        }
+
+        self.sign(&serde_json::Value::Object(claims))
+    }
+
+    fn issue_tokens(&self, grant: &Grant) -> anyhow::Result<http::Response> {
+        let now = chrono::Utc::now().timestamp();
+        let expires_in = self.expires_in();
+        let sub = self.state.borrow().user.sub.clone();
+
+        let access_token = self.sign(&serde_json::json!({
+            "iss": ISSUER,
+            "aud": grant.client_id,
+            "sub": sub,
+            "scp": grant.scope,
+            "iat": now,
+            "exp": now + expires_in,
+        }))?;
+
+        let id_token = self.id_token(grant)?;
+        let refresh_token = random_token();
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.access_tokens.insert(access_token.clone(), now + expires_in);
+            state.refresh_tokens.insert(refresh_token.clone(), grant.clone());
+        }
+
+        http::Response::from_json(
+            StatusCode::OK,
+            &serde_json::json!({
+                "access_token": access_token,
+                "token_type": "Bearer",
+                "expires_in": expires_in,
+                "refresh_token": refresh_token,
+                "scope": grant.scope,
+                "id_token": id_token,
+            }),
+        )
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.3
+    fn token(&self, request: &http::Request) -> anyhow::Result<http::Response> {
+        if self.has_failure(Failure::Unavailable) {
+            return oauth_error(StatusCode::SERVICE_UNAVAILABLE, "temporarily_unavailable");
+        }
+
+        if self.has_failure(Failure::RateLimited) {
+            return Ok(oauth_error(StatusCode::TOO_MANY_REQUESTS, "temporarily_unavailable")?
+                .with_header(reqwest::header::RETRY_AFTER.as_str(), "1"));
+        }
+
+        if self.has_failure(Failure::InvalidGrant) {
+            return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+        }
+
+        let params: HashMap<String, String> = request.form().into_iter().collect();
+        let param = |key: Params| params.get(&key.to_string()).cloned().unwrap_or_default();
+
+        match param(Params::GrantType).as_str() {
+            "authorization_code" => {
+                let Some(grant) = self.state.borrow_mut().codes.remove(&param(Params::Code)) else {
+                    return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+                };
+
+                // https://datatracker.ietf.org/doc/html/rfc7636#section-4.6
+                let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(param(Params::CodeVerifier).as_bytes()));
+
+                if grant.client_id != param(Params::ClientId)
+                    || grant.redirect_uri != param(Params::RedirectUri)
+                    || grant.code_challenge != code_challenge
+                {
+                    return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+                }
+
+                self.issue_tokens(&grant)
+            }
+            // https://datatracker.ietf.org/doc/html/rfc6749#section-6
+            "refresh_token" => {
+                let Some(grant) = self.state.borrow_mut().refresh_tokens.remove(&param(Params::RefreshToken)) else {
+                    return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+                };
+
+                if grant.client_id != param(Params::ClientId) {
+                    return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+                }
+
+                self.issue_tokens(&Grant { nonce: None, ..grant })
+            }
+            _ => oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type"),
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7009#section-2.2
+    fn revoke(&self, request: &http::Request) -> anyhow::Result<http::Response> {
+        let params: HashMap<String, String> = request.form().into_iter().collect();
+
+        if let Some(token) = params.get("token") {
+            let mut state = self.state.borrow_mut();
+            state.refresh_tokens.remove(token);
+            state.access_tokens.remove(token);
+        }
+
+        Ok(http::Response::new(StatusCode::OK, Vec::new()))
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#UserInfo
+    fn userinfo(&self, request: &http::Request) -> anyhow::Result<http::Response> {
+        let access_token = request
+            .header(reqwest::header::AUTHORIZATION.as_str())
+            .and_then(|authorization| authorization.split_once(' '))
+            .map(|(_, access_token)| access_token.to_owned())
+            .unwrap_or_default();
+
+        let expires_at = self.state.borrow().access_tokens.get(&access_token).copied();
+
+        if expires_at.is_none_or(|expires_at| chrono::Utc::now().timestamp() >= expires_at) {
+            return Ok(http::Response::new(StatusCode::UNAUTHORIZED, Vec::new())
+                .with_header(reqwest::header::WWW_AUTHENTICATE.as_str(), r#"Bearer error="invalid_token""#));
+        }
+
+        http::Response::from_json(StatusCode::OK, &self.user_claims()?)
+    }
+
+    fn discovery(&self) -> anyhow::Result<http::Response> {
+        http::Response::from_json(
+            StatusCode::OK,
+            &serde_json::json!({
+                "issuer": ISSUER,
+                "authorization_endpoint": endpoint(AUTHORIZATION_PATH),
+                "token_endpoint": endpoint(TOKEN_PATH),
+                "jwks_uri": endpoint(JWKS_PATH),
+                "userinfo_endpoint": endpoint(USERINFO_PATH),
+                "revocation_endpoint": endpoint(REVOCATION_PATH),
+                "end_session_endpoint": endpoint(END_SESSION_PATH),
+                "code_challenge_methods_supported": ["S256"],
+                "authorization_response_iss_parameter_supported": true,
+            }),
+        )
+    }
+
+    fn jwks(&self) -> anyhow::Result<http::Response> {
+        let state = self.state.borrow();
+
+        http::Response::from_json(
+            StatusCode::OK,
+            &serde_json::json!({
+                "keys": [{
+                    "kty": "oct",
+                    "kid": state.key.kid,
+                    "alg": "HS256",
+                    "k": URL_SAFE_NO_PAD.encode(&state.key.secret),
+                }],
+            }),
+        )
+    }
+
+    fn handle(&self, request: &http::Request) -> anyhow::Result<http::Response> {
+        let url = Url::parse(&request.url)?;
+
+        if !request.url.starts_with(ISSUER) {
+            return Ok(http::Response::new(StatusCode::NOT_FOUND, Vec::new()));
+        }
+
+        match (&request.method, url.path()) {
+            (&Method::GET, "/.well-known/openid-configuration") => self.discovery(),
+            (&Method::GET, JWKS_PATH) => self.jwks(),
+            (&Method::POST, TOKEN_PATH) => self.token(request),
+            (&Method::POST, REVOCATION_PATH) => self.revoke(request),
+            (&Method::GET | &Method::POST, USERINFO_PATH) => self.userinfo(request),
+            _ => Ok(http::Response::new(StatusCode::NOT_FOUND, Vec::new())),
+        }
+    }
+}
+
+impl http::HttpClient for MockProvider {
+    fn send(&self, request: http::Request) -> LocalBoxFuture<'_, anyhow::Result<http::Response>> {
+        Box::pin(async move { self.handle(&request) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::oauth2::{error, params, retry, token};
+
+    const CLIENT_ID: &str = "client";
+    const ORIGIN: &str = "http://localhost:8080";
+
+    fn provider() -> MockProvider {
+        MockProvider::new(claims::UserProfile {
+            sub: "user".to_owned(),
+            preferred_username: Some("user@mock-provider.test".to_owned()),
+            ..Default::default()
+        })
+    }
+
+    fn login(flow: &azure::AuthorizationCodeFlowWithPKCE) {
+        block_on(flow.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+        assert!(flow.is_authenticated.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn acquires_the_token_of_the_login() {
+        let provider = provider();
+        let flow = provider.flow(CLIENT_ID, "User.Read", ORIGIN);
+
+        login(&flow);
+
+        let token_record = block_on(flow.acquire_token_silent()).unwrap().unwrap();
+
+        assert_eq!(token_record.provider, ISSUER);
+        assert_eq!(token_record.scopes, ["User.Read"]);
+        assert_eq!(token_record.redirect_uri.as_deref(), Some(ORIGIN));
+        assert_eq!(block_on(flow.fetch_userinfo()).unwrap().sub, "user");
+    }
+
+    #[test]
+    fn new_logins_drop_the_tokens_of_the_previous_grant() {
+        const RESOURCE: &str = "api://resource";
+
+        let provider = provider();
+        let flow = provider.flow(CLIENT_ID, "User.Read", ORIGIN).with_resource(RESOURCE);
+        let subject = |token_record: token::TokenRecord| {
+            token::decode_jwt_payload::<serde_json::Value>(&token_record.access_token).unwrap()["sub"].clone()
+        };
+
+        login(&flow);
+        let token_record = block_on(flow.acquire_token_silent_for(RESOURCE)).unwrap().unwrap();
+        assert_eq!(subject(token_record), "user");
+
+        provider.set_user(claims::UserProfile {
+            sub: "other".to_owned(),
+            ..Default::default()
+        });
+        login(&flow);
+
+        let token_record = block_on(flow.acquire_token_silent_for(RESOURCE)).unwrap().unwrap();
+        assert_eq!(subject(token_record), "other");
+    }
+
+    #[test]
+    fn refreshes_expired_tokens() {
+        let provider = provider();
+        let flow = provider.flow(CLIENT_ID, "User.Read", ORIGIN);
+
+        provider.inject(Failure::ExpiredTokens);
+        login(&flow);
+        provider.clear_failures();
+
+        let refreshed = block_on(flow.acquire_token_silent()).unwrap().unwrap();
+
+        assert!(!refreshed.is_expired(flow.clock()));
+        assert_eq!(provider.state.borrow().access_tokens.len(), 2);
+        assert_eq!(provider.state.borrow().refresh_tokens.len(), 1);
+    }
+
+    #[test]
+    fn rejected_grants_end_the_session() {
+        let provider = provider();
+        let flow = provider.flow(CLIENT_ID, "User.Read", ORIGIN);
+
+        provider.inject(Failure::ExpiredTokens);
+        login(&flow);
+        provider.inject(Failure::InvalidGrant);
+
+        // a browser that leaves for the provider, the interactive login continues on the next page load
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let flow = flow.with_platform(platform.clone());
+
+        assert!(block_on(flow.acquire_token_silent()).unwrap().is_none());
+        assert!(!flow.has_cached_token());
+
+        let navigation = platform.navigations().pop().unwrap();
+        assert!(navigation.starts_with(&endpoint(AUTHORIZATION_PATH)));
+    }
+
+    #[test]
+    fn rate_limited_refreshes_keep_the_session() {
+        let provider = provider();
+        let flow = provider
+            .flow(CLIENT_ID, "User.Read", ORIGIN)
+            .with_retry_policy(retry::RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_secs(2),
+            });
+
+        provider.inject(Failure::ExpiredTokens);
+        login(&flow);
+        provider.inject(Failure::RateLimited);
+
+        let error = block_on(flow.acquire_token_silent()).unwrap_err();
+
+        assert!(!error::ends_session(&error));
+        assert!(flow.has_cached_token());
+    }
+
+    #[test]
+    fn rejects_id_tokens_with_a_wrong_nonce() {
+        let provider = provider();
+        let platform = provider.platform(ORIGIN);
+        let flow = provider
+            .flow(CLIENT_ID, "User.Read", ORIGIN)
+            .with_platform(platform.clone())
+            .with_hybrid_flow();
+
+        provider.inject(Failure::WrongNonce);
+
+        block_on(flow.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+
+        assert!(!flow.is_authenticated.load(Ordering::Acquire));
+        assert!(!flow.has_cached_token());
+        assert_eq!(platform.navigations().last().map(String::as_str), Some("/forbidden"));
+    }
+
+    #[test]
+    fn checks_max_age_against_the_verified_id_token() {
+        let provider = provider();
+        let flow = provider.flow(CLIENT_ID, "User.Read", ORIGIN).with_hybrid_flow();
+        let options = params::AuthorizationOptions::default().with_max_age(Duration::from_secs(60));
+
+        block_on(flow.login_with_redirect(&options)).unwrap();
+        assert!(flow.is_authenticated.load(Ordering::Acquire));
+
+        let mut user = provider.state.borrow().user.clone();
+        user.auth_time = Some(chrono::Utc::now().timestamp() - 3600);
+        provider.set_user(user);
+
+        let error = block_on(flow.login_with_redirect(&options)).unwrap_err();
+
+        assert_eq!(error.to_string(), "authentication is older than the requested max_age");
+    }
+
+    #[test]
+    fn rejects_id_tokens_signed_with_rotated_keys() {
+        let provider = provider();
+        let platform = platform::FakePlatform::new(ORIGIN);
+        let flow = provider
+            .flow(CLIENT_ID, "User.Read", ORIGIN)
+            .with_platform(platform.clone())
+            .with_hybrid_flow();
+
+        block_on(flow.login_with_redirect(&params::AuthorizationOptions::default())).unwrap();
+
+        // the front channel id token is signed before the provider rotates its keys
+        let authorize_url = Url::parse(&platform.navigations()[0]).unwrap();
+        let callback_url = provider.navigate(&authorize_url).unwrap();
+        provider.rotate_keys();
+
+        let platform = platform.with_current_url(callback_url);
+        let error = block_on(flow.login_with_redirect(&params::AuthorizationOptions::default())).unwrap_err();
+
+        assert!(error.to_string().contains("not available in jwks"));
+        assert!(!flow.is_authenticated.load(Ordering::Acquire));
+        assert!(!flow.has_cached_token());
+        assert!(platform.is_history_cleaned());
+    }
+}
@@ -1,7 +1,28 @@
 pub mod azure;
+pub mod claims;
+pub mod clock;
 pub mod csrf;
+pub mod deep_link;
+pub mod device;
+pub mod dpop;
+pub mod error;
+pub mod http;
+pub mod jar;
+pub mod jarm;
+pub mod jwks;
+pub mod loopback;
+// in-process provider for driving the flow in tests
+#[cfg(any(test, feature = "test-support"))]
+pub mod mock;
 pub mod oidc;
+pub mod par;
 pub mod params;
 pub mod pkce;
+pub mod platform;
+pub mod rar;
+pub mod requirements;
+pub mod retry;
 pub mod storage;
+pub mod time;
 pub mod token;
+pub mod transaction;
@@ -1,16 +1,35 @@
 use serde::Deserialize;
 
+use super::http;
+use super::retry;
+
 #[derive(Debug, Deserialize)]
 pub struct Configuration {
+    pub issuer: String,
+    pub jwks_uri: Option<String>,
     pub token_endpoint: String,
     pub authorization_endpoint: String,
+    pub end_session_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    pub device_authorization_endpoint: Option<String>,
+    pub pushed_authorization_request_endpoint: Option<String>,
+    #[serde(default)]
+    pub authorization_response_iss_parameter_supported: bool,
+    #[serde(default)]
+    pub request_object_signing_alg_values_supported: Vec<String>,
 }
 
 impl Configuration {
     // TODO implement cache each 12h
-    pub async fn from_remote(oidc_url: &'static str) -> anyhow::Result<Self> {
-        let client = reqwest::Client::new();
-        let conf = client.get(oidc_url).send().await?.json::<Self>().await?;
+    pub async fn from_remote(
+        http_client: &dyn http::HttpClient,
+        retry_policy: &retry::RetryPolicy,
+        oidc_url: &'static str,
+    ) -> anyhow::Result<Self> {
+        let conf = retry::send(retry_policy, || http_client.send(http::Request::get(oidc_url)))
+            .await?
+            .error_for_status()?
+            .json::<Self>()?;
         Ok(conf)
     }
 }
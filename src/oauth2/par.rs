@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+use super::http;
+
+// https://datatracker.ietf.org/doc/html/rfc9126#section-2.2
+#[derive(Debug, Deserialize)]
+pub struct PushedAuthorizationResponse {
+    pub request_uri: String,
+    pub expires_in: i64,
+}
+
+impl PushedAuthorizationResponse {
+    pub async fn request(
+        http_client: &dyn http::HttpClient,
+        endpoint: &str,
+        params: &[(String, String)],
+    ) -> anyhow::Result<Self> {
+        let response = http_client
+            .send(http::Request::post_form(endpoint, params))
+            .await?
+            .error_for_status()?
+            .json::<Self>()?;
+
+        Ok(response)
+    }
+}
@@ -1,4 +1,8 @@
 use derive_more::Display;
+use dioxus::logger::tracing;
+use std::time::Duration;
+
+use super::rar;
 
 #[derive(Debug, Display)]
 pub enum Params {
@@ -28,4 +32,175 @@ pub enum Params {
     RefreshToken,
     #[display("nonce")]
     Nonce,
+    #[display("id_token_hint")]
+    IdTokenHint,
+    #[display("post_logout_redirect_uri")]
+    PostLogoutRedirectUri,
+    #[display("prompt")]
+    Prompt,
+    #[display("login_hint")]
+    LoginHint,
+    #[display("domain_hint")]
+    DomainHint,
+    #[display("max_age")]
+    MaxAge,
+    #[display("acr_values")]
+    AcrValues,
+    #[display("ui_locales")]
+    UiLocales,
+    #[display("request_uri")]
+    RequestUri,
+    #[display("resource")]
+    Resource,
+    #[display("authorization_details")]
+    AuthorizationDetails,
+    #[display("request")]
+    Request,
+    #[display("subject_token")]
+    SubjectToken,
+    #[display("subject_token_type")]
+    SubjectTokenType,
+    #[display("requested_token_type")]
+    RequestedTokenType,
+    #[display("audience")]
+    Audience,
+    #[display("device_code")]
+    DeviceCode,
+}
+
+// set by the flow or the typed options, extra query parameters can't override them
+const RESERVED_PARAMS: &[Params] = &[
+    Params::ClientId,
+    Params::ResponseType,
+    Params::RedirectUri,
+    Params::ResponseMode,
+    Params::Scope,
+    Params::State,
+    Params::Nonce,
+    Params::CodeChallenge,
+    Params::CodeChallengeMethod,
+    Params::Prompt,
+    Params::LoginHint,
+    Params::DomainHint,
+    Params::MaxAge,
+    Params::AcrValues,
+    Params::UiLocales,
+    Params::RequestUri,
+    Params::Resource,
+    Params::AuthorizationDetails,
+    Params::Request,
+];
+
+// https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest
+#[derive(Debug, Display, Clone, Copy, PartialEq)]
+pub enum Prompt {
+    #[display("none")]
+    None,
+    #[display("login")]
+    Login,
+    #[display("consent")]
+    Consent,
+    #[display("select_account")]
+    SelectAccount,
+}
+
+/// Optional parameters of a single authorization request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuthorizationOptions {
+    pub prompt: Option<Prompt>,
+    pub login_hint: Option<String>,
+    pub domain_hint: Option<String>,
+    pub max_age: Option<Duration>,
+    pub acr_values: Option<String>,
+    pub ui_locales: Option<String>,
+    pub extra_query_parameters: Vec<(String, String)>,
+    pub authorization_details: Vec<serde_json::Value>,
+}
+
+impl AuthorizationOptions {
+    pub fn with_prompt(mut self, prompt: Prompt) -> Self {
+        self.prompt = Some(prompt);
+        self
+    }
+
+    pub fn with_login_hint(mut self, s: impl Into<String>) -> Self {
+        self.login_hint = Some(s.into());
+        self
+    }
+
+    pub fn with_domain_hint(mut self, s: impl Into<String>) -> Self {
+        self.domain_hint = Some(s.into());
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_acr_values(mut self, s: impl Into<String>) -> Self {
+        self.acr_values = Some(s.into());
+        self
+    }
+
+    pub fn with_ui_locales(mut self, s: impl Into<String>) -> Self {
+        self.ui_locales = Some(s.into());
+        self
+    }
+
+    /// Adds a provider specific parameter, protocol parameters such as `state` or `redirect_uri` are skipped.
+    pub fn with_extra_query_parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_query_parameters.push((key.into(), value.into()));
+        self
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9396#section-3
+    pub fn with_authorization_detail<T: rar::AuthorizationDetail>(mut self, detail: &T) -> anyhow::Result<Self> {
+        self.authorization_details.push(detail.to_value()?);
+        Ok(self)
+    }
+
+    pub fn to_params(&self) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+
+        if let Some(prompt) = self.prompt {
+            params.push((Params::Prompt.to_string(), prompt.to_string()));
+        }
+
+        if let Some(login_hint) = &self.login_hint {
+            params.push((Params::LoginHint.to_string(), login_hint.clone()));
+        }
+
+        if let Some(domain_hint) = &self.domain_hint {
+            params.push((Params::DomainHint.to_string(), domain_hint.clone()));
+        }
+
+        if let Some(max_age) = self.max_age {
+            params.push((Params::MaxAge.to_string(), max_age.as_secs().to_string()));
+        }
+
+        if let Some(acr_values) = &self.acr_values {
+            params.push((Params::AcrValues.to_string(), acr_values.clone()));
+        }
+
+        if let Some(ui_locales) = &self.ui_locales {
+            params.push((Params::UiLocales.to_string(), ui_locales.clone()));
+        }
+
+        if !self.authorization_details.is_empty() {
+            let authorization_details = serde_json::Value::from(self.authorization_details.clone());
+            params.push((Params::AuthorizationDetails.to_string(), authorization_details.to_string()));
+        }
+
+        for (key, value) in &self.extra_query_parameters {
+            if RESERVED_PARAMS.iter().any(|reserved| reserved.to_string() == *key) {
+                tracing::warn!("extra query parameter {key} is reserved, skipping it");
+                continue;
+            }
+
+            params.push((key.clone(), value.clone()));
+        }
+
+        params
+    }
 }
@@ -11,6 +11,12 @@ pub struct CodeVerifier {
     pub value: String,
 }
 
+impl Default for CodeVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeVerifier {
     pub fn new() -> Self {
         let mut buffer = [0u8; 64];
@@ -25,21 +31,8 @@ impl CodeVerifier {
         self.value.as_str()
     }
 
-    pub fn retrieve(storage_type: storage::StorageType) -> anyhow::Result<Self> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn retrieve(storage_type: &storage::StorageType) -> anyhow::Result<Self> {
+        let storage = storage::get(storage_type)?;
 
         if let Ok(Some(state)) = storage.get_item(PKCE_CODE_VERIFIER_KEY) {
             return Ok(Self { value: state });
@@ -48,21 +41,8 @@ impl CodeVerifier {
         anyhow::bail!("no pkce code verifier available");
     }
 
-    pub fn persist(&self, storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn persist(&self, storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.set_item(PKCE_CODE_VERIFIER_KEY, &self.value) else {
             anyhow::bail!("failed to save pkce code verifier");
@@ -71,21 +51,8 @@ impl CodeVerifier {
         Ok(())
     }
 
-    pub fn unpersist(storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn unpersist(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
         let Ok(_) = storage.remove_item(PKCE_CODE_VERIFIER_KEY) else {
             anyhow::bail!("failed to remove pkce code verifier");
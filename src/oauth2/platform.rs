@@ -0,0 +1,283 @@
+use anyhow::anyhow;
+use futures::future::LocalBoxFuture;
+#[cfg(any(test, feature = "test-support"))]
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use url::Url;
+use wasm_bindgen::{JsCast, closure::Closure};
+
+/// User agent driving the authorization redirect, the browser window on web builds.
+pub trait Platform: fmt::Debug {
+    /// Origin of the app, used as post logout redirect uri.
+    fn origin(&self) -> anyhow::Result<String>;
+
+    /// Redirect uri of a new authorization request, native platforms start listening for the response here.
+    fn redirect_uri(&self) -> anyhow::Result<String> {
+        self.origin()
+    }
+
+    /// Url the app was opened with, carrying the authorization response after a redirect.
+    fn current_url(&self) -> anyhow::Result<Option<Url>>;
+
+    fn navigate(&self, url: &str) -> anyhow::Result<()>;
+
+    /// Removes the authorization response from the current url.
+    fn clean_history(&self) -> anyhow::Result<()>;
+
+    /// Waits for the authorization response when it reaches the running app instead of a new page load.
+    fn wait_for_callback(&self) -> LocalBoxFuture<'_, anyhow::Result<Option<Url>>>;
+
+    /// Whether the network is reachable, assumed when the platform can't tell.
+    fn is_online(&self) -> bool {
+        true
+    }
+
+    /// Calls back with the new connectivity whenever it changes.
+    fn watch_connectivity(&self, _callback: Rc<dyn Fn(bool)>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlatformRef(Rc<dyn Platform>);
+
+impl PlatformRef {
+    pub fn new(platform: impl Platform + 'static) -> Self {
+        Self(Rc::new(platform))
+    }
+}
+
+// browsers on wasm, the system browser with a loopback redirect on native targets
+impl Default for PlatformRef {
+    #[cfg(target_arch = "wasm32")]
+    fn default() -> Self {
+        Self::new(WebPlatform)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        Self::new(super::loopback::LoopbackPlatform::default())
+    }
+}
+
+impl Deref for PlatformRef {
+    type Target = dyn Platform;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebPlatform;
+
+impl WebPlatform {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn window() -> anyhow::Result<web_sys::Window> {
+        anyhow::bail!("window not available outside the browser");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn window() -> anyhow::Result<web_sys::Window> {
+        let Some(window) = web_sys::window() else {
+            anyhow::bail!("window not available");
+        };
+
+        Ok(window)
+    }
+}
+
+impl Platform for WebPlatform {
+    fn origin(&self) -> anyhow::Result<String> {
+        let Ok(origin) = Self::window()?.location().origin() else {
+            anyhow::bail!("location origin not available");
+        };
+
+        Ok(origin)
+    }
+
+    fn current_url(&self) -> anyhow::Result<Option<Url>> {
+        let Ok(href) = Self::window()?.location().href() else {
+            anyhow::bail!("location href not available");
+        };
+
+        Ok(Some(Url::parse(&href)?))
+    }
+
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        Self::window()?.location().set_href(url).map_err(|err| anyhow!("{err:?}"))
+    }
+
+    fn clean_history(&self) -> anyhow::Result<()> {
+        let window = Self::window()?;
+
+        let path = window
+            .location()
+            .pathname()
+            .map_err(|err| anyhow!("failed to get pathname: {:?}", err))?;
+
+        if let Ok(history) = window.history() {
+            history
+                .replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&path))
+                .map_err(|err| anyhow!("failed to replace state: {:?}", err))?;
+        }
+
+        Ok(())
+    }
+
+    // the page unloads on navigation, the response arrives with the next page load
+    fn wait_for_callback(&self) -> LocalBoxFuture<'_, anyhow::Result<Option<Url>>> {
+        Box::pin(async { Ok(None) })
+    }
+
+    // https://html.spec.whatwg.org/multipage/system-state.html#navigator.online
+    fn is_online(&self) -> bool {
+        Self::window().map(|window| window.navigator().on_line()).unwrap_or(true)
+    }
+
+    fn watch_connectivity(&self, callback: Rc<dyn Fn(bool)>) -> anyhow::Result<()> {
+        let window = Self::window()?;
+
+        for (event, is_online) in [("online", true), ("offline", false)] {
+            let callback = callback.clone();
+            let listener = Closure::<dyn Fn()>::new(move || callback(is_online));
+
+            window
+                .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .map_err(|err| anyhow!("failed to listen to {event} events: {err:?}"))?;
+
+            // listens for the lifetime of the app
+            listener.forget();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+type Responder = Rc<dyn Fn(&Url) -> Option<Url>>;
+
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Default)]
+struct FakeState {
+    origin: String,
+    current_url: Option<Url>,
+    callback_url: Option<Url>,
+    responder: Option<Responder>,
+    navigations: Vec<String>,
+    history_cleaned: bool,
+    offline: bool,
+    connectivity_watchers: Vec<Rc<dyn Fn(bool)>>,
+}
+
+/// Scripted user agent to drive the redirect and callback handling off-browser.
+/// Clones share the recorded navigations, so a handle can be kept after passing it to `with_platform`.
+#[cfg(any(test, feature = "test-support"))]
+#[derive(Clone, Default)]
+pub struct FakePlatform {
+    state: Rc<RefCell<FakeState>>,
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl fmt::Debug for FakePlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+
+        f.debug_struct("FakePlatform")
+            .field("origin", &state.origin)
+            .field("current_url", &state.current_url)
+            .field("navigations", &state.navigations)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl FakePlatform {
+    pub fn new(origin: &str) -> Self {
+        let platform = Self::default();
+        platform.state.borrow_mut().origin = origin.to_owned();
+        platform
+    }
+
+    /// Url the app is loaded with, e.g. the redirect back from the provider.
+    pub fn with_current_url(self, url: Url) -> Self {
+        self.state.borrow_mut().current_url = Some(url);
+        self
+    }
+
+    /// Answers navigations to the authorize endpoint, the returned url is delivered as callback.
+    pub fn with_responder(self, responder: impl Fn(&Url) -> Option<Url> + 'static) -> Self {
+        self.state.borrow_mut().responder = Some(Rc::new(responder));
+        self
+    }
+
+    pub fn navigations(&self) -> Vec<String> {
+        self.state.borrow().navigations.clone()
+    }
+
+    pub fn is_history_cleaned(&self) -> bool {
+        self.state.borrow().history_cleaned
+    }
+
+    /// Switches the connectivity, notifying the watchers like the browser online and offline events.
+    pub fn set_online(&self, is_online: bool) {
+        let watchers = {
+            let mut state = self.state.borrow_mut();
+            state.offline = !is_online;
+            state.connectivity_watchers.clone()
+        };
+
+        for watcher in watchers {
+            watcher(is_online);
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-support"))]
+impl Platform for FakePlatform {
+    fn origin(&self) -> anyhow::Result<String> {
+        Ok(self.state.borrow().origin.clone())
+    }
+
+    fn current_url(&self) -> anyhow::Result<Option<Url>> {
+        Ok(self.state.borrow().current_url.clone())
+    }
+
+    fn navigate(&self, url: &str) -> anyhow::Result<()> {
+        let responder = self.state.borrow().responder.clone();
+
+        let callback_url = match (Url::parse(url), responder) {
+            (Ok(url), Some(responder)) => responder(&url),
+            _ => None,
+        };
+
+        let mut state = self.state.borrow_mut();
+        state.navigations.push(url.to_owned());
+        state.callback_url = callback_url;
+
+        Ok(())
+    }
+
+    fn clean_history(&self) -> anyhow::Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.current_url = None;
+        state.history_cleaned = true;
+
+        Ok(())
+    }
+
+    fn wait_for_callback(&self) -> LocalBoxFuture<'_, anyhow::Result<Option<Url>>> {
+        Box::pin(async move { Ok(self.state.borrow_mut().callback_url.take()) })
+    }
+
+    fn is_online(&self) -> bool {
+        !self.state.borrow().offline
+    }
+
+    fn watch_connectivity(&self, callback: Rc<dyn Fn(bool)>) -> anyhow::Result<()> {
+        self.state.borrow_mut().connectivity_watchers.push(callback);
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Serialize, de::DeserializeOwned};
+
+const TYPE_FIELD: &str = "type";
+
+// https://datatracker.ietf.org/doc/html/rfc9396#section-2
+pub trait AuthorizationDetail: Serialize + DeserializeOwned {
+    const TYPE: &'static str;
+
+    fn to_value(&self) -> anyhow::Result<serde_json::Value> {
+        let mut value = serde_json::to_value(self)?;
+
+        let Some(object) = value.as_object_mut() else {
+            anyhow::bail!("authorization detail {} must serialize to an object", Self::TYPE);
+        };
+
+        object.insert(TYPE_FIELD.to_owned(), serde_json::Value::from(Self::TYPE));
+
+        Ok(value)
+    }
+
+    fn from_values(values: &[serde_json::Value]) -> Vec<Self> {
+        values
+            .iter()
+            .filter(|value| value.get(TYPE_FIELD).and_then(|kind| kind.as_str()) == Some(Self::TYPE))
+            .filter_map(|value| serde_json::from_value(value.clone()).ok())
+            .collect()
+    }
+}
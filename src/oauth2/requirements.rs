@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use super::claims;
+
+/// Claims the signed in user must hold to see the guarded content.
+/// Every listed role, group and scope is required.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Requirements {
+    pub roles: &'static [&'static str],
+    pub groups: &'static [&'static str],
+    pub scopes: &'static [&'static str],
+    pub max_auth_age: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    Forbidden(String),
+    /// The user must sign in again within the maximum authentication age, with the reason why.
+    StaleAuthentication(Duration, String),
+}
+
+impl Requirements {
+    pub const fn new() -> Self {
+        Self {
+            roles: &[],
+            groups: &[],
+            scopes: &[],
+            max_auth_age: None,
+        }
+    }
+
+    pub const fn with_roles(mut self, roles: &'static [&'static str]) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    pub const fn with_groups(mut self, groups: &'static [&'static str]) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    pub const fn with_scopes(mut self, scopes: &'static [&'static str]) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Requires the user to have signed in within the given duration, otherwise a `prompt=login` is triggered.
+    pub const fn require_recent_auth(mut self, max_auth_age: Duration) -> Self {
+        self.max_auth_age = Some(max_auth_age);
+        self
+    }
+
+    /// Checks the claims and scopes of the session, `now` being the unix time on the provider clock.
+    pub fn check(
+        &self,
+        user_profile: Option<&claims::UserProfile>,
+        granted_scopes: &[String],
+        now: i64,
+    ) -> Result<(), Rejection> {
+        if !self.roles.is_empty() || !self.groups.is_empty() || self.max_auth_age.is_some() {
+            let Some(user_profile) = user_profile else {
+                return Err(Rejection::Forbidden("id token claims not available".to_owned()));
+            };
+
+            if let Some(role) = self.roles.iter().find(|role| !user_profile.roles.iter().any(|r| r == *role)) {
+                return Err(Rejection::Forbidden(format!("missing role {role}")));
+            }
+
+            if let Some(group) = self
+                .groups
+                .iter()
+                .find(|group| !user_profile.groups.iter().any(|g| g == *group))
+            {
+                return Err(Rejection::Forbidden(format!("missing group {group}")));
+            }
+
+            if let Some(max_auth_age) = self.max_auth_age {
+                // azure only issues auth_time when max_age is requested, signing in again with it provides the claim
+                let Some(auth_time) = user_profile.auth_time else {
+                    return Err(Rejection::StaleAuthentication(
+                        max_auth_age,
+                        "claim auth_time not available".to_owned(),
+                    ));
+                };
+
+                if now > auth_time + max_auth_age.as_secs() as i64 {
+                    return Err(Rejection::StaleAuthentication(
+                        max_auth_age,
+                        "authentication older than the required age".to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // azure returns the scopes prefixed with the application id uri, e.g. api://{client_id}/access
+        let is_granted = |scope: &str| {
+            granted_scopes
+                .iter()
+                .any(|granted| granted == scope || granted.ends_with(&format!("/{scope}")))
+        };
+
+        if let Some(scope) = self.scopes.iter().find(|scope| !is_granted(scope)) {
+            return Err(Rejection::Forbidden(format!("missing scope {scope}")));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    fn user_profile() -> claims::UserProfile {
+        claims::UserProfile {
+            sub: "user".to_owned(),
+            roles: vec!["Admin".to_owned()],
+            groups: vec!["Staff".to_owned()],
+            auth_time: Some(NOW - 60),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn requires_every_role_and_group() {
+        let user_profile = user_profile();
+
+        assert_eq!(
+            Requirements::new()
+                .with_roles(&["Admin"])
+                .check(Some(&user_profile), &[], NOW),
+            Ok(())
+        );
+        assert_eq!(
+            Requirements::new()
+                .with_roles(&["Admin", "Owner"])
+                .check(Some(&user_profile), &[], NOW),
+            Err(Rejection::Forbidden("missing role Owner".to_owned()))
+        );
+        assert_eq!(
+            Requirements::new()
+                .with_groups(&["Staff"])
+                .check(Some(&user_profile), &[], NOW),
+            Ok(())
+        );
+        assert_eq!(
+            Requirements::new()
+                .with_groups(&["Finance"])
+                .check(Some(&user_profile), &[], NOW),
+            Err(Rejection::Forbidden("missing group Finance".to_owned()))
+        );
+        assert_eq!(
+            Requirements::new().with_groups(&["Staff"]).check(None, &[], NOW),
+            Err(Rejection::Forbidden("id token claims not available".to_owned()))
+        );
+    }
+
+    #[test]
+    fn accepts_scopes_prefixed_with_the_application_id_uri() {
+        let granted_scopes = [
+            "api://00000000-0000-0000-0000-000000000000/access".to_owned(),
+            "openid".to_owned(),
+        ];
+
+        assert_eq!(
+            Requirements::new()
+                .with_scopes(&["access", "openid"])
+                .check(None, &granted_scopes, NOW),
+            Ok(())
+        );
+        assert_eq!(
+            Requirements::new().with_scopes(&["write"]).check(None, &granted_scopes, NOW),
+            Err(Rejection::Forbidden("missing scope write".to_owned()))
+        );
+        assert_eq!(
+            Requirements::new().with_scopes(&["cess"]).check(None, &granted_scopes, NOW),
+            Err(Rejection::Forbidden("missing scope cess".to_owned()))
+        );
+    }
+
+    #[test]
+    fn requires_a_recent_authentication() {
+        let max_auth_age = Duration::from_secs(15 * 60);
+        let requirements = Requirements::new().require_recent_auth(max_auth_age);
+
+        let mut user_profile = user_profile();
+        assert_eq!(requirements.check(Some(&user_profile), &[], NOW), Ok(()));
+
+        user_profile.auth_time = Some(NOW - 3600);
+        assert_eq!(
+            requirements.check(Some(&user_profile), &[], NOW),
+            Err(Rejection::StaleAuthentication(
+                max_auth_age,
+                "authentication older than the required age".to_owned()
+            ))
+        );
+
+        user_profile.auth_time = None;
+        assert_eq!(
+            requirements.check(Some(&user_profile), &[], NOW),
+            Err(Rejection::StaleAuthentication(
+                max_auth_age,
+                "claim auth_time not available".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use dioxus::logger::tracing;
+use rand::Rng;
+use std::time::Duration;
+
+use super::http;
+use super::time;
+
+/// Budget for retrying transient failures: network errors, 5xx and 429 responses.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    /// Longest wait between attempts, a longer `Retry-After` gives up instead.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // full jitter, https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        Duration::from_millis(rand::rng().random_range(0..=ceiling.as_millis() as u64))
+    }
+}
+
+// https://www.rfc-editor.org/rfc/rfc9110#section-10.2.3
+fn retry_after(response: &http::Response) -> Option<Duration> {
+    let retry_after = response.header(reqwest::header::RETRY_AFTER.as_str())?;
+
+    if let Ok(seconds) = retry_after.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // both dates come from the server clock
+    let date = chrono::DateTime::parse_from_rfc2822(retry_after).ok()?;
+    let now = response
+        .header(reqwest::header::DATE.as_str())
+        .and_then(|now| chrono::DateTime::parse_from_rfc2822(now).ok())
+        .map_or_else(|| chrono::Utc::now().timestamp(), |now| now.timestamp());
+    let seconds = (date.timestamp() - now).max(0);
+
+    Some(Duration::from_secs(seconds as u64))
+}
+
+/// Sends the request built by `send` until it succeeds, fails definitively or the budget runs out,
+/// returning the last response or error. The request is rebuilt on every attempt, e.g. for fresh dpop proofs.
+pub async fn send<F, Fut>(policy: &RetryPolicy, mut send: F) -> anyhow::Result<http::Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<http::Response>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        let result = send().await;
+
+        let delay = match &result {
+            Ok(response) if !response.is_transient() => return result,
+            Ok(response) => retry_after(response),
+            Err(_) => None,
+        };
+
+        if attempt >= policy.max_attempts {
+            return result;
+        }
+
+        let delay = delay.unwrap_or_else(|| policy.backoff(attempt));
+
+        if delay > policy.max_delay {
+            return result;
+        }
+
+        match &result {
+            Ok(response) => tracing::warn!(
+                "attempt {attempt} failed with status {}, retrying in {delay:?}",
+                response.status
+            ),
+            Err(err) => tracing::warn!("attempt {attempt} failed: {err}, retrying in {delay:?}"),
+        }
+
+        time::sleep(delay).await?;
+        attempt += 1;
+    }
+}
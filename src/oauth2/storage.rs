@@ -1,30 +1,201 @@
+use anyhow::anyhow;
+use dioxus::logger::tracing;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum StorageType {
     LocalStorage,
     SessionStorage,
+    /// Process memory of the client, for desktop builds without web storage.
+    Memory(MemoryStore),
+    /// Platform keychain registered with `register_secure_store`, for mobile builds.
+    Secure,
+}
+
+/// Key value store backing the oauth state, mirrors the web storage api.
+pub trait Store {
+    fn get_item(&self, key: &str) -> anyhow::Result<Option<String>>;
+    fn set_item(&self, key: &str, value: &str) -> anyhow::Result<()>;
+    fn remove_item(&self, key: &str) -> anyhow::Result<()>;
+    fn keys(&self) -> anyhow::Result<Vec<String>>;
+}
+
+impl Store for web_sys::Storage {
+    fn get_item(&self, key: &str) -> anyhow::Result<Option<String>> {
+        web_sys::Storage::get_item(self, key).map_err(|err| anyhow!("{err:?}"))
+    }
+
+    fn set_item(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        web_sys::Storage::set_item(self, key, value).map_err(|err| anyhow!("{err:?}"))
+    }
+
+    fn remove_item(&self, key: &str) -> anyhow::Result<()> {
+        web_sys::Storage::remove_item(self, key).map_err(|err| anyhow!("{err:?}"))
+    }
+
+    fn keys(&self) -> anyhow::Result<Vec<String>> {
+        let length = self.length().map_err(|err| anyhow!("{err:?}"))?;
+
+        Ok((0..length).filter_map(|index| self.key(index).ok().flatten()).collect())
+    }
 }
 
+// web storage only exists in the browser, native builds keep the state in memory
 impl Default for StorageType {
+    #[cfg(target_arch = "wasm32")]
     fn default() -> Self {
         Self::LocalStorage
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn default() -> Self {
+        Self::Memory(MemoryStore::default())
+    }
 }
 
-pub fn local_storage() -> Option<web_sys::Storage> {
-    if let Some(window) = web_sys::window() {
-        if let Ok(storage) = window.local_storage() {
-            return storage;
+/// Items kept by a single client, its clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    items: Rc<RefCell<BTreeMap<String, String>>>,
+}
+
+impl Store for MemoryStore {
+    fn get_item(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.items.borrow().get(key).cloned())
+    }
+
+    fn set_item(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        self.items.borrow_mut().insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove_item(&self, key: &str) -> anyhow::Result<()> {
+        self.items.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.items.borrow().keys().cloned().collect())
+    }
+}
+
+static SECURE_STORE: Mutex<Option<Box<dyn Store + Send>>> = Mutex::new(None);
+
+/// Process memory standing in for the secure store until the shell registers one, the session ends with the app.
+#[derive(Debug, Default)]
+struct UnregisteredStore {
+    items: RefCell<BTreeMap<String, String>>,
+}
+
+impl Store for UnregisteredStore {
+    fn get_item(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.items.borrow().get(key).cloned())
+    }
+
+    fn set_item(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        self.items.borrow_mut().insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn remove_item(&self, key: &str) -> anyhow::Result<()> {
+        self.items.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.items.borrow().keys().cloned().collect())
+    }
+}
+
+/// Registers the secure store provided by the app shell, e.g. the ios keychain or the android keystore.
+/// Until then the secure storage falls back to process memory.
+pub fn register_secure_store(store: impl Store + Send + 'static) {
+    if let Ok(mut secure_store) = SECURE_STORE.lock() {
+        *secure_store = Some(Box::new(store));
+    }
+}
+
+pub struct SecureStore;
+
+impl SecureStore {
+    fn with<T>(f: impl FnOnce(&dyn Store) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let mut secure_store = SECURE_STORE.lock().map_err(|err| anyhow!("{err}"))?;
+
+        let store = secure_store.get_or_insert_with(|| {
+            tracing::warn!("no secure store registered, the session is kept in memory");
+            Box::new(UnregisteredStore::default())
+        });
+
+        f(store.as_ref())
+    }
+}
+
+impl Store for SecureStore {
+    fn get_item(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Self::with(|store| store.get_item(key))
+    }
+
+    fn set_item(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        Self::with(|store| store.set_item(key, value))
+    }
+
+    fn remove_item(&self, key: &str) -> anyhow::Result<()> {
+        Self::with(|store| store.remove_item(key))
+    }
+
+    fn keys(&self) -> anyhow::Result<Vec<String>> {
+        Self::with(|store| store.keys())
+    }
+}
+
+pub fn get(storage_type: &StorageType) -> anyhow::Result<Box<dyn Store>> {
+    match storage_type {
+        StorageType::LocalStorage => {
+            let Some(storage) = local_storage() else {
+                anyhow::bail!("no local storage available");
+            };
+            Ok(Box::new(storage))
+        }
+        StorageType::SessionStorage => {
+            let Some(storage) = session_storage() else {
+                anyhow::bail!("no session storage available");
+            };
+            Ok(Box::new(storage))
         }
+        StorageType::Memory(memory_store) => Ok(Box::new(memory_store.clone())),
+        StorageType::Secure => Ok(Box::new(SecureStore)),
+    }
+}
+
+// web_sys panics outside of a javascript host instead of returning no window
+#[cfg(target_arch = "wasm32")]
+fn window() -> Option<web_sys::Window> {
+    web_sys::window()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn window() -> Option<web_sys::Window> {
+    None
+}
+
+pub fn local_storage() -> Option<web_sys::Storage> {
+    if let Some(window) = window()
+        && let Ok(storage) = window.local_storage()
+    {
+        return storage;
     }
     None
 }
 
 pub fn session_storage() -> Option<web_sys::Storage> {
-    if let Some(window) = web_sys::window() {
-        if let Ok(storage) = window.session_storage() {
-            return storage;
-        }
+    if let Some(window) = window()
+        && let Ok(storage) = window.session_storage()
+    {
+        return storage;
     }
     None
 }
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: Duration) -> anyhow::Result<()> {
+    use anyhow::anyhow;
+
+    let Some(window) = web_sys::window() else {
+        anyhow::bail!("window not available");
+    };
+
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let _ = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, duration.as_millis() as i32);
+    });
+
+    wasm_bindgen_futures::JsFuture::from(promise)
+        .await
+        .map_err(|err| anyhow!("{err:?}"))?;
+
+    Ok(())
+}
+
+// runtime agnostic, the desktop and mobile renderers drive their own executors
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) -> anyhow::Result<()> {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        let _ = sender.send(());
+    });
+
+    receiver.await?;
+
+    Ok(())
+}
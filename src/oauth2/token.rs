@@ -1,150 +1,392 @@
 use std::str::FromStr;
 
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
-use serde::{Deserialize, Serialize};
+use dioxus::logger::tracing;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::time::Duration;
 
+use super::clock;
+use super::rar;
 use super::storage;
 
-const TOKEN_RESPONSE_KEY: &str = "oauth_token_response";
-const TOKEN_RESPONSE_EXPIRATION_KEY: &str = "oauth_token_response_expiration";
+const TOKEN_RECORD_KEY: &str = "oauth_token_record";
+const TOKEN_RECORD_VERSION: u32 = 1;
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+// superseded by the token record, only read to migrate existing sessions
+const LEGACY_TOKEN_RESPONSE_KEY: &str = "oauth_token_response";
+const LEGACY_TOKEN_RESPONSE_EXPIRATION_KEY: &str = "oauth_token_response_expiration";
+
+// https://datatracker.ietf.org/doc/html/rfc8693#section-3
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default, PartialEq)]
 pub struct IdToken {
     pub sub: String,
+    /// Only present in id tokens of requests carrying a nonce, e.g. the hybrid flow.
+    #[serde(default)]
     pub nonce: String,
+    pub auth_time: Option<i64>,
 }
 
 impl FromStr for IdToken {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some(parts) = s.split('.').nth(1) else {
-            return Err("cant extract jwt parts".to_owned());
-        };
-
-        let decoded_parts = URL_SAFE_NO_PAD.decode(parts.as_bytes()).map_err(|e| e.to_string())?;
-        let serialized = String::from_utf8(decoded_parts).map_err(|e| e.to_string())?;
-        serde_json::from_str::<Self>(&serialized).map_err(|e| e.to_string())
+        decode_jwt_payload(s)
     }
 }
 
+pub fn decode_jwt_payload<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    let Some(parts) = s.split('.').nth(1) else {
+        return Err("cant extract jwt parts".to_owned());
+    };
+
+    let decoded_parts = URL_SAFE_NO_PAD.decode(parts.as_bytes()).map_err(|e| e.to_string())?;
+    let serialized = String::from_utf8(decoded_parts).map_err(|e| e.to_string())?;
+    serde_json::from_str::<T>(&serialized).map_err(|e| e.to_string())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub access_token: String,
     pub expires_in: i64,
+    #[serde(default)]
     pub ext_expires_in: i64,
+    #[serde(default)]
     pub refresh_token: String,
+    /// Lifetime of the refresh token, azure announces it for single-page applications.
+    pub refresh_token_expires_in: Option<i64>,
+    #[serde(default)]
     pub scope: String,
     pub token_type: String,
+    pub issued_token_type: Option<String>,
     pub id_token: Option<String>,
+    // https://datatracker.ietf.org/doc/html/rfc9396#section-7
+    #[serde(default)]
+    pub authorization_details: Vec<serde_json::Value>,
 }
 
-impl TokenResponse {
-    pub fn retrieve(storage_type: storage::StorageType) -> anyhow::Result<Option<Self>> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
+/// Tokens of a grant as kept in storage, expiry is absolute on the calibrated clock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenRecord {
+    /// Format of the record, missing in records written before it was introduced.
+    #[serde(default)]
+    pub version: u32,
+    /// Issuer of the provider that granted the tokens, empty for records migrated from the legacy format.
+    pub provider: String,
+    pub access_token: String,
+    pub token_type: String,
+    pub issued_token_type: Option<String>,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+    pub scopes: Vec<String>,
+    pub issued_at: i64,
+    pub expires_at: i64,
+    /// Hard expiry of the session, unknown unless announced by the provider or configured.
+    pub refresh_expires_at: Option<i64>,
+    /// Thumbprint of the dpop key the token is bound to.
+    pub dpop_jkt: Option<String>,
+    /// Redirect uri the grant was authorized with, sent again when refreshing.
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+    pub authorization_details: Vec<serde_json::Value>,
+    /// Expired token handed out while offline, its refresh is queued until connectivity returns.
+    #[serde(skip)]
+    pub is_stale: bool,
+}
+
+#[derive(Deserialize)]
+struct Versioned {
+    #[serde(default)]
+    version: u32,
+}
+
+// the legacy format persisted the token response along with the key binding
+#[derive(Deserialize)]
+struct LegacyTokenResponse {
+    #[serde(flatten)]
+    token_response: TokenResponse,
+    #[serde(default)]
+    dpop_jkt: Option<String>,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8707, tokens requested for a resource are cached apart
+fn storage_key(key: &str, resource: Option<&str>) -> String {
+    match resource {
+        Some(resource) => format!("{key}:{resource}"),
+        None => key.to_owned(),
+    }
+}
+
+impl TokenRecord {
+    pub fn new(provider: &str, token_response: TokenResponse, issued_at: i64) -> Self {
+        Self {
+            version: TOKEN_RECORD_VERSION,
+            provider: provider.to_owned(),
+            access_token: token_response.access_token,
+            token_type: token_response.token_type,
+            issued_token_type: token_response.issued_token_type,
+            refresh_token: Some(token_response.refresh_token).filter(|refresh_token| !refresh_token.is_empty()),
+            id_token: token_response.id_token,
+            scopes: token_response.scope.split_whitespace().map(ToOwned::to_owned).collect(),
+            issued_at,
+            expires_at: issued_at + token_response.expires_in,
+            refresh_expires_at: token_response
+                .refresh_token_expires_in
+                .map(|refresh_token_expires_in| issued_at + refresh_token_expires_in),
+            dpop_jkt: None,
+            redirect_uri: None,
+            authorization_details: token_response.authorization_details,
+            is_stale: false,
+        }
+    }
+
+    /// Whether the access token expires within the skew of the clock.
+    pub fn is_expired(&self, clock: &clock::Clock) -> bool {
+        clock.now() + clock.skew_secs() >= self.expires_at
+    }
+
+    /// Lifetime left to the access token, zero once expired.
+    pub fn expires_in_remaining(&self, clock: &clock::Clock) -> Duration {
+        Duration::from_secs((self.expires_at - clock.now()).max(0) as u64)
+    }
+
+    /// Whether the refresh token expires within the skew of the clock, a new login is needed then.
+    pub fn is_refresh_expired(&self, clock: &clock::Clock) -> bool {
+        self.refresh_expires_at
+            .is_some_and(|refresh_expires_at| clock.now() + clock.skew_secs() >= refresh_expires_at)
+    }
+
+    /// Lifetime left to the refresh token, if known.
+    pub fn refresh_expires_in_remaining(&self, clock: &clock::Clock) -> Option<Duration> {
+        self.refresh_expires_at
+            .map(|refresh_expires_at| Duration::from_secs((refresh_expires_at - clock.now()).max(0) as u64))
+    }
+
+    /// Authorization details of the given type granted by the provider.
+    pub fn granted_authorization_details<T: rar::AuthorizationDetail>(&self) -> Vec<T> {
+        T::from_values(&self.authorization_details)
+    }
+
+    /// Record still valid for at least the skew of the clock.
+    pub fn retrieve(
+        storage_type: &storage::StorageType,
+        resource: Option<&str>,
+        clock: &clock::Clock,
+    ) -> anyhow::Result<Option<Self>> {
+        Ok(Self::retrieve_unchecked(storage_type, resource)?.filter(|token_record| !token_record.is_expired(clock)))
+    }
+
+    /// Record even if expired, check `is_expired` before using its access token.
+    pub fn retrieve_unchecked(storage_type: &storage::StorageType, resource: Option<&str>) -> anyhow::Result<Option<Self>> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(Some(token_record)) = storage.get_item(&storage_key(TOKEN_RECORD_KEY, resource)) else {
+            return Self::migrate(storage_type, resource);
         };
 
-        if let Ok(Some(token_response)) = storage.get_item(TOKEN_RESPONSE_KEY) {
-            if let Ok(Some(token_response_expiration)) = storage.get_item(TOKEN_RESPONSE_EXPIRATION_KEY) {
-                if let Ok(expires_at) = token_response_expiration.parse::<i64>() {
-                    if chrono::Utc::now().timestamp() < expires_at {
-                        return Ok(Some(serde_json::from_str(&token_response)?));
-                    }
+        match serde_json::from_str::<Versioned>(&token_record).map(|versioned| versioned.version) {
+            Ok(TOKEN_RECORD_VERSION) => {
+                if let Ok(token_record) = serde_json::from_str(&token_record) {
+                    return Ok(Some(token_record));
                 }
             }
+            // records written before the version was introduced are upgraded as long as they still parse
+            Ok(0) => {
+                if let Ok(token_record) = serde_json::from_str::<Self>(&token_record) {
+                    let token_record = Self {
+                        version: TOKEN_RECORD_VERSION,
+                        ..token_record
+                    };
+
+                    token_record.persist(storage_type, resource)?;
+
+                    return Ok(Some(token_record));
+                }
+            }
+            _ => {}
         }
 
+        // records of another version or unreadable are dropped, the session then starts over
+        tracing::warn!("discarding unreadable token record");
+
+        let Ok(_) = storage.remove_item(&storage_key(TOKEN_RECORD_KEY, resource)) else {
+            anyhow::bail!("failed to remove token record");
+        };
+
         Ok(None)
     }
 
-    pub fn retrieve_unchecked(storage_type: storage::StorageType) -> anyhow::Result<Option<Self>> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
+    // sessions started before the token record kept the response and its expiration under two keys
+    fn migrate(storage_type: &storage::StorageType, resource: Option<&str>) -> anyhow::Result<Option<Self>> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(Some(token_response)) = storage.get_item(&storage_key(LEGACY_TOKEN_RESPONSE_KEY, resource)) else {
+            return Ok(None);
         };
 
-        if let Ok(Some(token_response)) = storage.get_item(TOKEN_RESPONSE_KEY) {
-            return Ok(Some(serde_json::from_str(&token_response)?));
-        }
+        let expires_at = storage
+            .get_item(&storage_key(LEGACY_TOKEN_RESPONSE_EXPIRATION_KEY, resource))
+            .ok()
+            .flatten()
+            .and_then(|expires_at| expires_at.parse::<i64>().ok())
+            .unwrap_or_default();
 
-        Ok(None)
-    }
+        let LegacyTokenResponse {
+            token_response,
+            dpop_jkt,
+        } = serde_json::from_str(&token_response)?;
+        let expires_in = token_response.expires_in;
 
-    pub fn persist(&self, storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
+        let token_record = Self {
+            expires_at,
+            dpop_jkt,
+            ..Self::new("", token_response, expires_at - expires_in)
         };
 
-        let Ok(_) = storage.set_item(TOKEN_RESPONSE_KEY, &serde_json::to_string(&self)?) else {
-            anyhow::bail!("failed to save token response");
+        token_record.persist(storage_type, resource)?;
+
+        let Ok(_) = storage.remove_item(&storage_key(LEGACY_TOKEN_RESPONSE_KEY, resource)) else {
+            anyhow::bail!("failed to remove token response");
+        };
+        let Ok(_) = storage.remove_item(&storage_key(LEGACY_TOKEN_RESPONSE_EXPIRATION_KEY, resource)) else {
+            anyhow::bail!("failed to remove token expiration");
         };
 
-        let delta = 5; /* delay time for computing */
-        let expires_at = chrono::Utc::now().timestamp() + self.expires_in - delta;
+        Ok(Some(token_record))
+    }
+
+    pub fn persist(&self, storage_type: &storage::StorageType, resource: Option<&str>) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
-        let Ok(_) = storage.set_item(TOKEN_RESPONSE_EXPIRATION_KEY, &expires_at.to_string()) else {
-            anyhow::bail!("failed to save token expiration");
+        let Ok(_) = storage.set_item(&storage_key(TOKEN_RECORD_KEY, resource), &serde_json::to_string(&self)?) else {
+            anyhow::bail!("failed to save token record");
         };
 
         Ok(())
     }
 
-    pub fn unpersist(&self, storage_type: storage::StorageType) -> anyhow::Result<()> {
-        let storage = match storage_type {
-            storage::StorageType::LocalStorage => {
-                let Some(storage) = storage::local_storage() else {
-                    anyhow::bail!("no local storage available");
-                };
-                storage
-            }
-            storage::StorageType::SessionStorage => {
-                let Some(storage) = storage::session_storage() else {
-                    anyhow::bail!("no session storage available");
-                };
-                storage
-            }
-        };
+    pub fn unpersist(&self, storage_type: &storage::StorageType, resource: Option<&str>) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
 
-        let Ok(_) = storage.remove_item(TOKEN_RESPONSE_KEY) else {
-            anyhow::bail!("failed to remove token response");
-        };
-        let Ok(_) = storage.remove_item(TOKEN_RESPONSE_EXPIRATION_KEY) else {
-            anyhow::bail!("failed to remove token expiration");
+        let Ok(_) = storage.remove_item(&storage_key(TOKEN_RECORD_KEY, resource)) else {
+            anyhow::bail!("failed to remove token record");
         };
 
         Ok(())
     }
+
+    /// Removes the tokens of every resource and exchange kept in the cache, legacy entries included.
+    pub fn unpersist_all(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
+
+        let keys = storage
+            .keys()?
+            .into_iter()
+            .filter(|key| key.starts_with(TOKEN_RECORD_KEY) || key.starts_with(LEGACY_TOKEN_RESPONSE_KEY));
+
+        for key in keys {
+            let Ok(_) = storage.remove_item(&key) else {
+                anyhow::bail!("failed to remove token record");
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_response() -> TokenResponse {
+        serde_json::from_value(serde_json::json!({
+            "access_token": "access",
+            "expires_in": 3600,
+            "refresh_token": "refresh",
+            "refresh_token_expires_in": 86400,
+            "scope": "openid profile",
+            "token_type": "Bearer",
+        }))
+        .unwrap()
+    }
+
+    fn memory_storage() -> storage::StorageType {
+        storage::StorageType::Memory(storage::MemoryStore::default())
+    }
+
+    #[test]
+    fn round_trips_through_storage() {
+        let storage_type = memory_storage();
+        let token_record = TokenRecord::new("https://issuer.test", token_response(), 1_000);
+
+        token_record.persist(&storage_type, Some("api://resource")).unwrap();
+
+        let retrieved = TokenRecord::retrieve_unchecked(&storage_type, Some("api://resource"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(retrieved.version, TOKEN_RECORD_VERSION);
+        assert_eq!(retrieved.provider, "https://issuer.test");
+        assert_eq!(retrieved.access_token, "access");
+        assert_eq!(retrieved.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(retrieved.scopes, ["openid", "profile"]);
+        assert_eq!(retrieved.expires_at, 4_600);
+        assert_eq!(retrieved.refresh_expires_at, Some(87_400));
+        assert!(TokenRecord::retrieve_unchecked(&storage_type, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn upgrades_records_without_version() {
+        let storage_type = memory_storage();
+        let mut token_record = serde_json::to_value(TokenRecord::new("https://issuer.test", token_response(), 1_000)).unwrap();
+        token_record.as_object_mut().unwrap().remove("version");
+
+        let storage = storage::get(&storage_type).unwrap();
+        storage.set_item(TOKEN_RECORD_KEY, &token_record.to_string()).unwrap();
+
+        let retrieved = TokenRecord::retrieve_unchecked(&storage_type, None).unwrap().unwrap();
+
+        assert_eq!(retrieved.version, TOKEN_RECORD_VERSION);
+        assert_eq!(retrieved.access_token, "access");
+
+        let persisted: Versioned = serde_json::from_str(&storage.get_item(TOKEN_RECORD_KEY).unwrap().unwrap()).unwrap();
+        assert_eq!(persisted.version, TOKEN_RECORD_VERSION);
+    }
+
+    #[test]
+    fn discards_unreadable_records() {
+        let storage_type = memory_storage();
+        let storage = storage::get(&storage_type).unwrap();
+
+        for token_record in ["not json", r#"{"version": 99}"#, r#"{"access_token": "access"}"#] {
+            storage.set_item(TOKEN_RECORD_KEY, token_record).unwrap();
+
+            assert!(TokenRecord::retrieve_unchecked(&storage_type, None).unwrap().is_none());
+            assert!(storage.get_item(TOKEN_RECORD_KEY).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn migrates_the_legacy_token_response() {
+        let storage_type = memory_storage();
+        let storage = storage::get(&storage_type).unwrap();
+
+        let mut legacy = serde_json::to_value(token_response()).unwrap();
+        legacy["dpop_jkt"] = "thumbprint".into();
+
+        storage.set_item(LEGACY_TOKEN_RESPONSE_KEY, &legacy.to_string()).unwrap();
+        storage.set_item(LEGACY_TOKEN_RESPONSE_EXPIRATION_KEY, "4600").unwrap();
+
+        let retrieved = TokenRecord::retrieve_unchecked(&storage_type, None).unwrap().unwrap();
+
+        assert_eq!(retrieved.version, TOKEN_RECORD_VERSION);
+        assert_eq!(retrieved.provider, "");
+        assert_eq!(retrieved.issued_at, 1_000);
+        assert_eq!(retrieved.expires_at, 4_600);
+        assert_eq!(retrieved.dpop_jkt.as_deref(), Some("thumbprint"));
+        assert!(storage.get_item(LEGACY_TOKEN_RESPONSE_KEY).unwrap().is_none());
+        assert!(storage.get_item(LEGACY_TOKEN_RESPONSE_EXPIRATION_KEY).unwrap().is_none());
+        assert!(storage.get_item(TOKEN_RECORD_KEY).unwrap().is_some());
+    }
 }
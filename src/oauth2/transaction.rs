@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use super::storage;
+
+const TRANSACTION_KEY: &str = "oauth_transaction";
+
+/// Parameters of the in-flight authorization request that must be checked on the callback.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Redirect uri sent to the authorize endpoint, the code exchange must send the same one.
+    pub redirect_uri: Option<String>,
+    pub max_age: Option<i64>,
+    pub issuer: Option<String>,
+}
+
+impl Transaction {
+    pub fn retrieve(storage_type: &storage::StorageType) -> anyhow::Result<Self> {
+        let storage = storage::get(storage_type)?;
+
+        if let Ok(Some(transaction)) = storage.get_item(TRANSACTION_KEY) {
+            return Ok(serde_json::from_str(&transaction)?);
+        }
+
+        anyhow::bail!("no transaction available");
+    }
+
+    pub fn persist(&self, storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(_) = storage.set_item(TRANSACTION_KEY, &serde_json::to_string(&self)?) else {
+            anyhow::bail!("failed to save transaction");
+        };
+
+        Ok(())
+    }
+
+    pub fn unpersist(storage_type: &storage::StorageType) -> anyhow::Result<()> {
+        let storage = storage::get(storage_type)?;
+
+        let Ok(_) = storage.remove_item(TRANSACTION_KEY) else {
+            anyhow::bail!("failed to remove transaction");
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+use dioxus::prelude::*;
+
+#[component]
+pub fn AdminPage() -> Element {
+    rsx! {
+        div {
+            class: "p-10 grid gap-5",
+
+            span {
+                "Administración"
+            }
+        }
+    }
+}
@@ -1,13 +1,19 @@
 use dioxus::prelude::*;
 
 #[component]
-pub fn ForbiddenPage() -> Element {
+pub fn ForbiddenPage(reason: String) -> Element {
     rsx! {
         div {
             class: "p-10 grid gap-5",
             p {
                "No tiene acceso. Hable con el administrador."
             }
+            if !reason.is_empty() {
+                p {
+                    class: "text-sm text-gray-500",
+                    "{reason}"
+                }
+            }
         }
     }
 }
@@ -1,14 +1,26 @@
 use dioxus::prelude::*;
 
+use crate::hooks::auth::{AuthState, use_auth};
+
 #[component]
 pub fn HomePage() -> Element {
+    let auth = use_auth();
+
+    use_hook(|| auth.fetch_userinfo());
+
+    let name = match auth.state() {
+        AuthState::Authenticated(Some(user_profile)) => user_profile.name.or(user_profile.preferred_username),
+        _ => None,
+    }
+    .unwrap_or("world".to_owned());
+
     rsx! {
         div {
             class: "p-10 grid gap-5",
 
             span {
                 class: "grid overflow-x-auto",
-                "Hello world!"
+                "Hello {name}!"
             }
         }
     }
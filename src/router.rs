@@ -1,8 +1,8 @@
 use dioxus::prelude::*;
 
 use crate::{
-    layouts::security::SecurityLayout,
-    pages::{forbidden::ForbiddenPage, home::HomePage},
+    layouts::{admin::AdminLayout, security::SecurityLayout},
+    pages::{admin::AdminPage, forbidden::ForbiddenPage, home::HomePage},
 };
 
 #[derive(Debug, Clone, Routable, PartialEq)]
@@ -11,8 +11,13 @@ pub enum Route {
     #[layout(SecurityLayout)]
         #[route("/", HomePage)]
         Home {},
+
+        #[layout(AdminLayout)]
+            #[route("/admin", AdminPage)]
+            Admin {},
+        #[end_layout]
     #[end_layout]
 
-    #[route("/forbidden", ForbiddenPage)]
-    Forbidden {},
+    #[route("/forbidden?:reason", ForbiddenPage)]
+    Forbidden { reason: String },
 }